@@ -95,8 +95,13 @@ fn main() {
         process::exit(4);
     }
 
-    for chan in dev.channels() {
-        let data: Vec<u16> = buf.channel_iter::<u16>(&chan).collect();
-        println!("{}: {:?}", chan.id().unwrap_or_default(), data);
+    for chan in buf.channels() {
+        match buf.try_channel_iter::<u16>(&chan) {
+            Ok(iter) => {
+                let data: Vec<u16> = iter.collect();
+                println!("{}: {:?}", chan.id().unwrap_or_default(), data);
+            }
+            Err(err) => eprintln!("{}: {}", chan.id().unwrap_or_default(), err),
+        }
     }
 }
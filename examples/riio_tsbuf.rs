@@ -197,12 +197,12 @@ fn run() -> Result<()> {
 
         // Extract and print the data
 
-        let ts_data = buf.channel_iter::<u64>(&ts_chan);
+        let ts_data = buf.try_channel_iter::<u64>(&ts_chan)?;
 
         // The timestamp is represented as a 64-bit integer number of
         // nanoseconds since the Unix Epoch. We convert to a Rust SystemTime,
         // then a chrono DataTime for pretty printing.
-        buf.channel_iter::<u16>(&sample_chan)
+        buf.try_channel_iter::<u16>(&sample_chan)?
             .zip(ts_data.map(|ts| {
                 DateTime::<Utc>::from(SystemTime::UNIX_EPOCH + Duration::from_nanos(ts))
                     .format("%T%.6f")
@@ -52,12 +52,20 @@
 
 use std::{
     any::Any,
+    cell::Cell,
     collections::HashMap,
+    convert::TryFrom,
     fmt::Display,
     marker::PhantomData,
     mem,
     os::raw::{c_int, c_longlong, c_void},
     ptr,
+    time::Duration,
+};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
 };
 
 use super::*;
@@ -77,6 +85,19 @@ pub struct Buffer {
     pub(crate) cap: usize,
     /// Copy of the device to which this device is attached.
     pub(crate) dev: Device,
+    /// The channels that were enabled when the buffer was created.
+    pub(crate) chans: Vec<Channel>,
+    /// The number of bytes of valid data in the buffer. This is updated
+    /// on each refill, which may return less than a full buffer.
+    pub(crate) filled: Cell<usize>,
+    /// Whether the buffer was created in cyclic mode
+    pub(crate) cyclic: bool,
+}
+
+/// Gets the number of bytes between the start and end of the data in
+/// a library buffer.
+pub(crate) fn buffer_data_len(buf: *mut ffi::iio_buffer) -> usize {
+    unsafe { ffi::iio_buffer_end(buf) as usize - ffi::iio_buffer_start(buf) as usize }
 }
 
 impl Buffer {
@@ -88,20 +109,201 @@ impl Buffer {
         self.cap
     }
 
-    /// Gets a reference to the device to which this buffer is attached.
-    pub fn device(&self) -> &Device {
-        &self.dev
+    /// Determines if the buffer was created in cyclic mode.
+    ///
+    /// A cyclic output buffer only needs to be pushed once, after which
+    /// the hardware repeats the samples until the buffer is destroyed.
+    /// This can't be queried from the library, so it's remembered from
+    /// when the buffer was created.
+    pub fn is_cyclic(&self) -> bool {
+        self.cyclic
+    }
+
+    /// Changes the number of samples that the buffer can hold.
+    ///
+    /// The library can't resize a buffer, so this consumes the buffer,
+    /// destroys the underlying buffer, and creates a new one, with the same
+    /// set of channels enabled as when this buffer was created. Any data in
+    /// the buffer is lost, so this should be called before the first
+    /// refill, or between captures. The enabled state of the device's
+    /// channels is restored afterward.
+    ///
+    /// On success, this returns the resized buffer. If the buffer can't be
+    /// created with the new size, the error is returned and the old buffer
+    /// is gone, so a new one must be created from the device.
+    pub fn set_samples_count(mut self, n: usize) -> Result<Buffer> {
+        if n == self.cap {
+            return Ok(self);
+        }
+
+        let dev = self.dev.clone();
+        let prev_enabled: Vec<(Channel, bool)> = dev
+            .channels()
+            .map(|ch| {
+                let enabled = ch.is_enabled();
+                (ch, enabled)
+            })
+            .collect();
+
+        for (ch, _) in &prev_enabled {
+            if self.chans.iter().any(|c| c.chan == ch.chan) {
+                ch.enable();
+            }
+            else {
+                ch.disable();
+            }
+        }
+
+        // The old buffer must go first, as devices typically only allow one.
+        // A null pointer here is only ever seen by drop().
+        unsafe { ffi::iio_buffer_destroy(self.buf) };
+        self.buf = unsafe { ffi::iio_device_create_buffer(dev.dev, n, self.cyclic) };
+        let res: Result<()> = if self.buf.is_null() {
+            Err(Errno::last().into())
+        }
+        else {
+            Ok(())
+        };
+
+        for (ch, enabled) in prev_enabled {
+            if enabled {
+                ch.enable();
+            }
+            else {
+                ch.disable();
+            }
+        }
+
+        res?;
+        self.cap = n;
+        self.filled.set(buffer_data_len(self.buf));
+        Ok(self)
+    }
+
+    /// Gets the step size of the buffer, in bytes.
+    ///
+    /// This is the distance between consecutive samples of a channel, which
+    /// is the size of one sample from all the enabled channels.
+    pub fn step(&self) -> usize {
+        unsafe { ffi::iio_buffer_step(self.buf) as usize }
+    }
+
+    /// Gets the number of bytes of data in the buffer.
+    ///
+    /// This is the length from the start to the end of the data from the
+    /// last refill, which might be less than the full capacity.
+    pub fn len(&self) -> usize {
+        self.filled.get()
+    }
+
+    /// Determines if the buffer has no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of samples in the buffer, per channel.
+    pub fn sample_count(&self) -> usize {
+        match self.step() {
+            0 => 0,
+            step => self.len() / step,
+        }
+    }
+
+    /// Gets a raw pointer to the start of the data in the buffer.
+    ///
+    /// This is intended for passing the block of samples to another native
+    /// library. The data is [`Buffer::byte_len()`] bytes long, in the
+    /// hardware format, with the samples of the enabled channels
+    /// interleaved.
+    ///
+    /// The pointer is only valid while the buffer is alive, and only until
+    /// the buffer is recreated by [`Buffer::set_samples_count()`]. The data
+    /// it points to is overwritten by each call to [`Buffer::refill()`].
+    pub fn as_ptr(&self) -> *const u8 {
+        unsafe { ffi::iio_buffer_start(self.buf) as *const u8 }
+    }
+
+    /// Gets a mutable raw pointer to the start of the data in the buffer.
+    ///
+    /// This is intended for filling an output buffer from another native
+    /// library before calling [`Buffer::push()`]. No more than
+    /// [`Buffer::byte_len()`] bytes should be written. The same validity
+    /// constraints apply as for [`Buffer::as_ptr()`].
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        unsafe { ffi::iio_buffer_start(self.buf) as *mut u8 }
+    }
+
+    /// Gets the length of the data at [`Buffer::as_ptr()`], in bytes.
+    ///
+    /// This is the same as [`Buffer::len()`].
+    pub fn byte_len(&self) -> usize {
+        self.len()
+    }
+
+    /// Gets the device to which this buffer is attached.
+    ///
+    /// The buffer already holds a handle to its device, so this is a cheap
+    /// way to get at the device's attributes or sample size without
+    /// having to pass the device around separately.
+    pub fn device(&self) -> Device {
+        self.dev.clone()
+    }
+
+    /// Gets an iterator over the channels that were enabled when the buffer
+    /// was created.
+    ///
+    /// These determine the layout of the samples in the buffer, and are
+    /// not affected by any channels enabled or disabled after the buffer
+    /// was created.
+    pub fn channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.chans.iter().cloned()
     }
 
     /// Gets a pollable file descriptor for the buffer.
     ///
     /// This can be used to determine when [`Buffer::refill()`] or
     /// [`Buffer::push()`] can be called without blocking.
+    ///
+    /// This returns an `ENOSYS` error if the backend doesn't support
+    /// polling.
     pub fn poll_fd(&self) -> Result<c_int> {
         let ret = unsafe { ffi::iio_buffer_get_poll_fd(self.buf) };
         sys_result(i32::from(ret), ret)
     }
 
+    /// Determines if the buffer provides a pollable file descriptor.
+    ///
+    /// This is typically true for the local backend, but not for others,
+    /// like the network backend. When it is false, event-driven waiting
+    /// with [`Buffer::wait_readable()`] isn't possible, and the application
+    /// should rely on blocking calls to [`Buffer::refill()`] instead.
+    pub fn supports_poll(&self) -> bool {
+        self.poll_fd().is_ok()
+    }
+
+    /// Waits up to the specified time for data to be ready to read.
+    ///
+    /// This polls the buffer's [file descriptor][Buffer::poll_fd()] and
+    /// returns `Ok(true)` if data is ready, or `Ok(false)` if the timeout
+    /// expired first. This allows for bounded waits without blocking
+    /// indefinitely in [`Buffer::refill()`].
+    ///
+    /// This is only meaningful for backends that provide a valid poll file
+    /// descriptor, such as the local backend. For other backends, this
+    /// returns an error. See [`Buffer::supports_poll()`].
+    pub fn wait_readable(&self, timeout: Duration) -> Result<bool> {
+        let fd = self.poll_fd().map_err(|err| match err.errno() {
+            Some(Errno::ENOSYS) => Error::General(
+                "The backend doesn't provide a pollable file descriptor for the buffer".into(),
+            ),
+            _ => err,
+        })?;
+        let ms = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+        let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+        let n = poll(&mut fds, ms)?;
+        Ok(n > 0)
+    }
+
     /// Make calls to [`Buffer::push()`] or [`Buffer::refill()`] blocking or not.
     ///
     /// A [`Device`] is blocking by default.
@@ -113,9 +315,15 @@ impl Buffer {
     /// Fetch more samples from the hardware.
     ///
     /// This is only valid for input buffers.
+    ///
+    /// On success, this returns the number of bytes read, which might be
+    /// less than the size of the buffer on some drivers. The sample
+    /// iterators are bounded by the number of bytes actually read.
     pub fn refill(&self) -> Result<usize> {
         let ret = unsafe { ffi::iio_buffer_refill(self.buf) };
-        sys_result(ret as i32, ret as usize)
+        let n = sys_result(ret as i32, ret as usize)?;
+        self.filled.set(n);
+        Ok(n)
     }
 
     /// Send the samples to the hardware.
@@ -216,23 +424,19 @@ impl Buffer {
             )
         };
         sys_result(ret as i32, ())?;
-        let s = unsafe {
-            CStr::from_ptr(buf.as_ptr())
-                .to_str()
-                .map_err(|_| Error::StringConversionError)?
-        };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()).to_str()? };
         Ok(s.into())
     }
 
     /// Reads a buffer-specific attribute as a boolean
     ///
+    /// This accepts the various encodings that devices use, such as
+    /// "1"/"0", "true"/"false", and "Y"/"N".
+    ///
     /// `attr` The name of the attribute
     pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
-        let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret =
-            unsafe { ffi::iio_device_buffer_attr_read_bool(self.dev.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let sval = self.attr_read_str(attr)?;
+        parse_bool(&sval)
     }
 
     /// Reads a buffer-specific attribute as an integer (i64)
@@ -273,24 +477,29 @@ impl Buffer {
 
     /// Writes a buffer-specific attribute
     ///
+    /// On success, this returns the number of bytes written.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<usize> {
         let sval = attr_to_string(val)?;
         self.attr_write_str(attr, &sval)
     }
 
     /// Writes a buffer-specific attribute as a string
     ///
+    /// On success, this returns the number of bytes written, which callers
+    /// can check to verify that the whole value was accepted.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<usize> {
         let attr = CString::new(attr)?;
         let sval = CString::new(val)?;
         let ret = unsafe {
             ffi::iio_device_buffer_attr_write(self.dev.dev, attr.as_ptr(), sval.as_ptr())
         };
-        sys_result(ret as i32, ())
+        sys_result(ret as i32, ret as usize)
     }
 
     /// Writes a buffer-specific attribute as a boolean
@@ -331,6 +540,34 @@ impl Buffer {
         AttrIterator { buf: self, idx: 0 }
     }
 
+    /// Gets the amount of data that is queued in the kernel, in bytes.
+    ///
+    /// This reads the `data_available` buffer attribute. For an input
+    /// buffer, this is the number of bytes that can be read without
+    /// blocking. For an output buffer, it is the number of bytes of empty
+    /// space that can be written. This can be used to decide when to call
+    /// [`Buffer::refill()`] or [`Buffer::push()`].
+    pub fn data_available(&self) -> Result<usize> {
+        let n = self.attr_read_int("data_available")?;
+        usize::try_from(n).map_err(|_| Error::BadReturnSize)
+    }
+
+    /// Gets the amount of room in an output buffer, in bytes, before a
+    /// call to [`Buffer::push()`] would block.
+    ///
+    /// For output buffers, the kernel reports the empty space in the
+    /// `data_available` buffer attribute. Generators, like those for a DAC,
+    /// can use this to pace their output. This depends on the backend and
+    /// driver providing the attribute, and is an error for input buffers.
+    pub fn space_available(&self) -> Result<usize> {
+        if !self.chans.iter().any(|ch| ch.is_output()) {
+            return Err(Error::General(
+                "Space available only applies to output buffers".into(),
+            ));
+        }
+        self.data_available()
+    }
+
     /// Set the number of kernel buffers for the device.
     pub fn set_num_kernel_buffers(&self, n: u32) -> Result<()> {
         let ret = unsafe { ffi::iio_device_set_kernel_buffers_count(self.dev.dev, n as c_uint) };
@@ -338,10 +575,38 @@ impl Buffer {
     }
 
     /// Gets an iterator for the data from a channel.
+    ///
+    /// The iterator only covers the data from the last refill, which might
+    /// be less than the full capacity of the buffer.
+    ///
+    /// This doesn't check that the size of `T` matches the size of the
+    /// channel's samples, so the wrong type silently produces misaligned
+    /// data. Use [`Buffer::try_channel_iter()`] instead, which does.
+    #[deprecated(
+        since = "0.4.0",
+        note = "doesn't check the sample size; use `try_channel_iter()` instead"
+    )]
     pub fn channel_iter<T>(&self, chan: &Channel) -> IntoIter<T> {
+        self.channel_iter_unchecked(chan)
+    }
+
+    /// Gets an iterator for the data from a channel, checking that the
+    /// size of `T` matches the size of the channel's samples.
+    ///
+    /// The iterator only covers the data from the last refill, which might
+    /// be less than the full capacity of the buffer.
+    pub fn try_channel_iter<T>(&self, chan: &Channel) -> Result<IntoIter<T>> {
+        validate_sample_type::<T>(&chan.data_format())?;
+        Ok(self.channel_iter_unchecked(chan))
+    }
+
+    // Gets an iterator for the data from a channel, without checking the
+    // size of `T`.
+    fn channel_iter_unchecked<T>(&self, chan: &Channel) -> IntoIter<T> {
         unsafe {
             let begin = ffi::iio_buffer_first(self.buf, chan.chan) as *mut T;
-            let end = ffi::iio_buffer_end(self.buf) as *const T;
+            let start = ffi::iio_buffer_start(self.buf) as *const u8;
+            let end = start.wrapping_add(self.filled.get()) as *const T;
             let ptr = begin;
             let step: isize = ffi::iio_buffer_step(self.buf) / mem::size_of::<T>() as isize;
 
@@ -353,12 +618,199 @@ impl Buffer {
             }
         }
     }
+
+    /// Gets the timestamps of the samples in the buffer.
+    ///
+    /// IIO devices with a `timestamp` channel produce a timestamp for each
+    /// sample as a 64-bit count of nanoseconds. This returns `None` if the
+    /// buffer doesn't include a timestamp channel.
+    pub fn timestamps(&self) -> Option<Vec<i64>> {
+        let chan = self
+            .chans
+            .iter()
+            .find(|ch| ch.channel_type() == ChannelType::Timestamp)?;
+        Some(self.try_channel_iter::<i64>(chan).ok()?.collect())
+    }
+
+    /// Reads the samples of all the channels in the buffer, as scaled values.
+    ///
+    /// This returns a matrix with a row for each channel and a column for
+    /// each sample. The rows are in the same order as the channels from
+    /// [`Buffer::channels()`]. Each sample is converted to the host format
+    /// and then to physical units as `(raw + offset) * scale`, using the
+    /// channel's [offset][Channel::offset()] and [scale][Channel::scale()],
+    /// if it has them.
+    pub fn read_matrix(&self) -> Result<Vec<Vec<f64>>> {
+        self.chans
+            .iter()
+            .map(|chan| {
+                let conv = PhysicalConversion::new(chan)?;
+                let row = read_as_f64(chan, self)?
+                    .into_iter()
+                    .map(|val| conv.apply(val))
+                    .collect();
+                Ok(row)
+            })
+            .collect()
+    }
+
+    /// Converts the samples of a channel to physical units, writing them
+    /// into a slice.
+    ///
+    /// This is for real-time processing loops, where the buffer is
+    /// refilled repeatedly and the output shouldn't be reallocated for each
+    /// window. The samples are decoded directly from the buffer and
+    /// converted with `conv`, which should be read from the channel with
+    /// [`PhysicalConversion::new()`] once, outside of the loop, so that no
+    /// attributes are read here.
+    ///
+    /// For a channel with a [repeat][DataFormat::repeat()] of more than
+    /// one, each sample has that many values, which are written in order.
+    /// The slice must be large enough to hold all the values from the last
+    /// refill. On success, this returns the number of values written.
+    pub fn convert_channel_into(
+        &self,
+        chan: &Channel,
+        conv: &PhysicalConversion,
+        out: &mut [f64],
+    ) -> Result<usize> {
+        if !self.chans.iter().any(|ch| ch.chan == chan.chan) {
+            return Err(Error::General(format!(
+                "The channel '{}' is not enabled in the buffer",
+                chan.id().unwrap_or_default()
+            )));
+        }
+
+        let fmt = chan.data_format();
+        let sz = (fmt.length() / 8) as usize;
+        let repeat = fmt.repeat().max(1) as usize;
+        let step = self.step();
+        if sz == 0 || step == 0 {
+            return Ok(0);
+        }
+
+        let n = (self.len() / step) * repeat;
+        if out.len() < n {
+            return Err(Error::General(format!(
+                "The output holds {} values, but the buffer has {}",
+                out.len(),
+                n
+            )));
+        }
+
+        unsafe {
+            let first = ffi::iio_buffer_first(self.buf, chan.chan) as *const u8;
+            for (i, val) in out[..n].iter_mut().enumerate() {
+                let p = first.add((i / repeat) * step + (i % repeat) * sz);
+                *val = conv.apply(fmt.decode(slice::from_raw_parts(p, sz)) as f64);
+            }
+        }
+        Ok(n)
+    }
+
+    /// Gets an iterator over the samples in the buffer, by time step, as
+    /// scaled values.
+    ///
+    /// Each item is a [`Sample`] holding the value of each of the enabled
+    /// channels at one instant, converted to physical units as
+    /// `(raw + offset) * scale`. This transposes the interleaved data in
+    /// the buffer into rows, like a synchronized set of sensor readings.
+    pub fn samples(&self) -> Result<SampleIterator<'_, f64>> {
+        let chans = self
+            .chans
+            .iter()
+            .map(|chan| {
+                let mut sc = SampleChannel::new(self, chan);
+                sc.conv = PhysicalConversion::new(chan)?;
+                Ok(sc)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SampleIterator::new(self, chans, |chan, val| {
+            chan.conv.apply(val as f64)
+        }))
+    }
+
+    /// Gets an iterator over the samples in the buffer, by time step, as
+    /// raw values.
+    ///
+    /// This is like [`Buffer::samples()`], but the values are only
+    /// converted to the host format, and not scaled.
+    pub fn samples_raw(&self) -> SampleIterator<'_, i64> {
+        let chans = self
+            .chans
+            .iter()
+            .map(|chan| SampleChannel::new(self, chan))
+            .collect();
+        SampleIterator::new(self, chans, |_, val| val)
+    }
+
+    /// Writes samples for a channel into the buffer.
+    ///
+    /// The samples are interleaved into the slots for the channel, in
+    /// preparation for a call to [`Buffer::push()`]. This is the output
+    /// counterpart to [`Buffer::channel_iter()`], and expects the samples
+    /// to already be in the hardware format.
+    ///
+    /// The size of `T` must match the size of the channel's samples. This
+    /// returns the number of samples written, which can be less than the
+    /// length of `data` if the buffer is full.
+    pub fn write_channel<T: Copy>(&mut self, chan: &Channel, data: &[T]) -> Result<usize> {
+        validate_sample_type::<T>(&chan.data_format())?;
+
+        let mut n = 0;
+        unsafe {
+            let mut ptr = ffi::iio_buffer_first(self.buf, chan.chan) as *mut u8;
+            let end = ffi::iio_buffer_end(self.buf) as *const u8;
+            let step = ffi::iio_buffer_step(self.buf);
+
+            for val in data {
+                if ptr as *const u8 >= end {
+                    break;
+                }
+                ptr::write_unaligned(ptr as *mut T, *val);
+                ptr = ptr.wrapping_offset(step);
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+}
+
+// Reads the converted samples of a channel, whatever their type, as f64's.
+fn read_as_f64(chan: &Channel, buf: &Buffer) -> Result<Vec<f64>> {
+    fn to_f64<T: Into<f64>>(v: Vec<T>) -> Vec<f64> {
+        v.into_iter().map(Into::into).collect()
+    }
+    let fmt = chan.data_format();
+    let v = match (fmt.byte_length(), fmt.is_signed()) {
+        (1, true) => to_f64(chan.read::<i8>(buf)?),
+        (1, false) => to_f64(chan.read::<u8>(buf)?),
+        (2, true) => to_f64(chan.read::<i16>(buf)?),
+        (2, false) => to_f64(chan.read::<u16>(buf)?),
+        (4, true) => to_f64(chan.read::<i32>(buf)?),
+        (4, false) => to_f64(chan.read::<u32>(buf)?),
+        (8, true) => chan
+            .read::<i64>(buf)?
+            .into_iter()
+            .map(|v| v as f64)
+            .collect(),
+        (8, false) => chan
+            .read::<u64>(buf)?
+            .into_iter()
+            .map(|v| v as f64)
+            .collect(),
+        _ => return Err(Error::WrongDataType),
+    };
+    Ok(v)
 }
 
 /// Destroy the underlying buffer when the object scope ends.
 impl Drop for Buffer {
     fn drop(&mut self) {
-        unsafe { ffi::iio_buffer_destroy(self.buf) }
+        // The buffer is null if it couldn't be recreated by a resize.
+        if !self.buf.is_null() {
+            unsafe { ffi::iio_buffer_destroy(self.buf) }
+        }
     }
 }
 
@@ -391,6 +843,113 @@ impl<T> Iterator for IntoIter<T> {
     }
 }
 
+/// The values of all the channels in a buffer at a single time step.
+///
+/// See [`Buffer::samples()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample<T> {
+    /// The channel ID's and values, in the order of the buffer's channels
+    pub values: Vec<(String, T)>,
+}
+
+impl<T: Copy> Sample<T> {
+    /// Gets the value of the channel with the specified ID.
+    pub fn get(&self, id: &str) -> Option<T> {
+        self.values
+            .iter()
+            .find(|(chan_id, _)| chan_id == id)
+            .map(|(_, val)| *val)
+    }
+}
+
+// The information needed to extract the samples of a channel.
+#[derive(Debug)]
+struct SampleChannel {
+    // The ID of the channel
+    id: String,
+    // The data format of the channel
+    fmt: DataFormat,
+    // The byte offset of the channel's first sample from the buffer start
+    first: usize,
+    // The conversion of a raw value to physical units
+    conv: PhysicalConversion,
+}
+
+impl SampleChannel {
+    fn new(buf: &Buffer, chan: &Channel) -> Self {
+        let first = unsafe {
+            ffi::iio_buffer_first(buf.buf, chan.chan) as usize
+                - ffi::iio_buffer_start(buf.buf) as usize
+        };
+        Self {
+            id: chan.id().unwrap_or_default(),
+            fmt: chan.data_format(),
+            first,
+            conv: PhysicalConversion::default(),
+        }
+    }
+}
+
+/// An iterator over the samples in a buffer, by time step.
+///
+/// See [`Buffer::samples()`] and [`Buffer::samples_raw()`].
+#[derive(Debug)]
+pub struct SampleIterator<'a, T> {
+    /// The buffer with the samples
+    buf: &'a Buffer,
+    /// The channels in each sample
+    chans: Vec<SampleChannel>,
+    /// Converts a raw value for the channel to an output value
+    conv: fn(&SampleChannel, i64) -> T,
+    /// The index of the next sample
+    idx: usize,
+    /// The number of samples in the buffer
+    n: usize,
+}
+
+impl<'a, T> SampleIterator<'a, T> {
+    fn new(buf: &'a Buffer, chans: Vec<SampleChannel>, conv: fn(&SampleChannel, i64) -> T) -> Self {
+        Self {
+            buf,
+            chans,
+            conv,
+            idx: 0,
+            n: buf.sample_count(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SampleIterator<'a, T> {
+    type Item = Sample<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.n {
+            return None;
+        }
+        let start = self.buf.as_ptr();
+        let base = self.idx * self.buf.step();
+        let values = self
+            .chans
+            .iter()
+            .map(|chan| {
+                let sz = chan.fmt.byte_length();
+                let bytes = unsafe { slice::from_raw_parts(start.add(base + chan.first), sz) };
+                let val = (self.conv)(chan, chan.fmt.decode(bytes));
+                (chan.id.clone(), val)
+            })
+            .collect();
+        self.idx += 1;
+        Some(Sample { values })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.n - self.idx;
+        (n, Some(n))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for SampleIterator<'a, T> {}
+
 /// Iterator over the buffer attributes
 /// 'a Lifetime of the Buffer
 #[derive(Debug)]
@@ -19,9 +19,13 @@ use std::{
     fmt::Display,
     mem,
     os::raw::{c_char, c_int, c_longlong, c_uint, c_void},
+    path::PathBuf,
     str::FromStr,
 };
 
+/// The sysfs directory containing the IIO devices, for local contexts.
+pub(crate) const SYSFS_IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
 /// The type of data associated with a channel.
 #[allow(missing_docs)]
 #[repr(u32)]
@@ -62,6 +66,58 @@ pub enum ChannelType {
     Unknown = ffi::iio_chan_type_IIO_CHAN_TYPE_UNKNOWN,
 }
 
+/// The modifier of a channel, which further specifies the type of its
+/// data, such as the axis of an accelerometer.
+#[allow(missing_docs)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modifier {
+    NoMod = ffi::iio_modifier_IIO_NO_MOD,
+    X = ffi::iio_modifier_IIO_MOD_X,
+    Y = ffi::iio_modifier_IIO_MOD_Y,
+    Z = ffi::iio_modifier_IIO_MOD_Z,
+    XAndY = ffi::iio_modifier_IIO_MOD_X_AND_Y,
+    XAndZ = ffi::iio_modifier_IIO_MOD_X_AND_Z,
+    YAndZ = ffi::iio_modifier_IIO_MOD_Y_AND_Z,
+    XAndYAndZ = ffi::iio_modifier_IIO_MOD_X_AND_Y_AND_Z,
+    XOrY = ffi::iio_modifier_IIO_MOD_X_OR_Y,
+    XOrZ = ffi::iio_modifier_IIO_MOD_X_OR_Z,
+    YOrZ = ffi::iio_modifier_IIO_MOD_Y_OR_Z,
+    XOrYOrZ = ffi::iio_modifier_IIO_MOD_X_OR_Y_OR_Z,
+    LightBoth = ffi::iio_modifier_IIO_MOD_LIGHT_BOTH,
+    LightIr = ffi::iio_modifier_IIO_MOD_LIGHT_IR,
+    RootSumSquaredXY = ffi::iio_modifier_IIO_MOD_ROOT_SUM_SQUARED_X_Y,
+    SumSquaredXYZ = ffi::iio_modifier_IIO_MOD_SUM_SQUARED_X_Y_Z,
+    LightClear = ffi::iio_modifier_IIO_MOD_LIGHT_CLEAR,
+    LightRed = ffi::iio_modifier_IIO_MOD_LIGHT_RED,
+    LightGreen = ffi::iio_modifier_IIO_MOD_LIGHT_GREEN,
+    LightBlue = ffi::iio_modifier_IIO_MOD_LIGHT_BLUE,
+    Quaternion = ffi::iio_modifier_IIO_MOD_QUATERNION,
+    TempAmbient = ffi::iio_modifier_IIO_MOD_TEMP_AMBIENT,
+    TempObject = ffi::iio_modifier_IIO_MOD_TEMP_OBJECT,
+    NorthMagn = ffi::iio_modifier_IIO_MOD_NORTH_MAGN,
+    NorthTrue = ffi::iio_modifier_IIO_MOD_NORTH_TRUE,
+    NorthMagnTiltComp = ffi::iio_modifier_IIO_MOD_NORTH_MAGN_TILT_COMP,
+    NorthTrueTiltComp = ffi::iio_modifier_IIO_MOD_NORTH_TRUE_TILT_COMP,
+    Running = ffi::iio_modifier_IIO_MOD_RUNNING,
+    Jogging = ffi::iio_modifier_IIO_MOD_JOGGING,
+    Walking = ffi::iio_modifier_IIO_MOD_WALKING,
+    Still = ffi::iio_modifier_IIO_MOD_STILL,
+    RootSumSquaredXYZ = ffi::iio_modifier_IIO_MOD_ROOT_SUM_SQUARED_X_Y_Z,
+    I = ffi::iio_modifier_IIO_MOD_I,
+    Q = ffi::iio_modifier_IIO_MOD_Q,
+    Co2 = ffi::iio_modifier_IIO_MOD_CO2,
+    Voc = ffi::iio_modifier_IIO_MOD_VOC,
+    LightUv = ffi::iio_modifier_IIO_MOD_LIGHT_UV,
+    LightDuv = ffi::iio_modifier_IIO_MOD_LIGHT_DUV,
+    Pm1 = ffi::iio_modifier_IIO_MOD_PM1,
+    Pm2p5 = ffi::iio_modifier_IIO_MOD_PM2P5,
+    Pm4 = ffi::iio_modifier_IIO_MOD_PM4,
+    Pm10 = ffi::iio_modifier_IIO_MOD_PM10,
+    Ethanol = ffi::iio_modifier_IIO_MOD_ETHANOL,
+    H2 = ffi::iio_modifier_IIO_MOD_H2,
+}
+
 /// The format of a data sample.
 #[derive(Debug, Copy, Clone)]
 pub struct DataFormat {
@@ -85,6 +141,18 @@ impl DataFormat {
         u32::from(self.data_fmt.bits)
     }
 
+    /// Gets the effective resolution of the sample, in bits.
+    ///
+    /// This is the real resolution of the converter, as opposed to the
+    /// storage width of the sample from [`DataFormat::length()`]. For
+    /// example, a 12-bit ADC typically stores its samples in 16 bits, with
+    /// the 12 valid bits located by the [shift][DataFormat::shift()]. The
+    /// remaining bits are padding or sign extension, so this is the value
+    /// to use for the full-scale range of a plot or for noise analysis.
+    pub fn effective_bits(&self) -> u32 {
+        self.bits()
+    }
+
     /// Right-shift to apply when converting sample.
     pub fn shift(&self) -> u32 {
         u32::from(self.data_fmt.shift)
@@ -126,6 +194,80 @@ impl DataFormat {
         nbytes as usize
     }
 
+    /// Decodes a single little-endian sample from raw buffer bytes.
+    ///
+    /// This extracts the valid bits of the sample, applying the shift and
+    /// mask, and sign-extends it if the format is signed. An unsigned
+    /// sample is zero-extended, so any bits above the valid ones are
+    /// cleared rather than copied from the top valid bit. Only the first
+    /// `length / 8` bytes of `bytes` are used.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `bytes` is shorter than a single sample.
+    pub fn decode_le(&self, bytes: &[u8]) -> i64 {
+        let raw = self
+            .sample_bytes(bytes)
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        self.extract(raw)
+    }
+
+    /// Decodes a single big-endian sample from raw buffer bytes.
+    ///
+    /// This is the same as [`DataFormat::decode_le()`], but for samples
+    /// in big-endian byte order.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `bytes` is shorter than a single sample.
+    pub fn decode_be(&self, bytes: &[u8]) -> i64 {
+        let raw = self
+            .sample_bytes(bytes)
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        self.extract(raw)
+    }
+
+    /// Decodes a single sample from raw buffer bytes, using the byte order
+    /// of the format.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `bytes` is shorter than a single sample.
+    pub fn decode(&self, bytes: &[u8]) -> i64 {
+        if self.is_big_endian() {
+            self.decode_be(bytes)
+        }
+        else {
+            self.decode_le(bytes)
+        }
+    }
+
+    // Gets the bytes for a single sample, up to 64 bits.
+    fn sample_bytes<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        let n = (self.length() / 8).min(8) as usize;
+        &bytes[..n]
+    }
+
+    // Shifts, masks, and sign-extends a raw sample.
+    fn extract(&self, raw: u64) -> i64 {
+        let val = raw.checked_shr(self.shift()).unwrap_or(0);
+        let bits = self.bits();
+        if bits == 0 || bits >= 64 {
+            return val as i64;
+        }
+        let mask = (1u64 << bits) - 1;
+        let val = val & mask;
+        if self.is_signed() && (val & (1u64 << (bits - 1))) != 0 {
+            (val | !mask) as i64
+        }
+        else {
+            val as i64
+        }
+    }
+
     /// Gets the TypeId for a single sample from the channel.
     ///
     /// This will get the TypeId for a sample if it can fit into a standard
@@ -154,17 +296,54 @@ impl DataFormat {
     }
 }
 
+/// Checks that a type is the same size as the samples of a data format.
+///
+/// This guards the typed sample readers and writers against accessing
+/// the samples of a channel with a type of the wrong size, which would
+/// produce misaligned data.
+pub(crate) fn validate_sample_type<T>(fmt: &DataFormat) -> Result<()> {
+    let type_size = size_of::<T>();
+    let sample_size = fmt.byte_length();
+    if type_size != sample_size {
+        return Err(Error::SampleSizeMismatch {
+            type_size,
+            sample_size,
+        });
+    }
+    Ok(())
+}
+
+/// The per-channel calibration values of a device.
+///
+/// Each value is optional, since a channel might expose only one of the
+/// `calibbias` and `calibscale` attributes, or neither of them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// The calibration bias (offset), from the `calibbias` attribute
+    pub bias: Option<i64>,
+    /// The calibration scale (gain), from the `calibscale` attribute
+    pub scale: Option<f64>,
+}
+
 /// An Industrial I/O Device Channel
 #[derive(Debug, Clone)]
 pub struct Channel {
     /// Pointer to the underlying IIO channel object
     pub(crate) chan: *mut ffi::iio_channel,
-    #[allow(dead_code)]
     /// Holder for the Device's lifetime for libiio safety.
     pub(crate) ctx: Context,
 }
 
 impl Channel {
+    /// Gets the device to which the channel belongs
+    pub fn device(&self) -> Device {
+        let dev = unsafe { ffi::iio_channel_get_device(self.chan) };
+        Device {
+            dev: dev as *mut ffi::iio_device,
+            ctx: self.ctx.clone(),
+        }
+    }
+
     /// Retrieves the name of the channel (e.g. <b><i>vccint</i></b>)
     pub fn name(&self) -> Option<String> {
         let pstr = unsafe { ffi::iio_channel_get_name(self.chan) };
@@ -177,6 +356,23 @@ impl Channel {
         cstring_opt(pstr)
     }
 
+    /// Retrieves the label of the channel, if it has one.
+    ///
+    /// Labels are typically assigned through the devicetree, and give
+    /// channels stable, human-readable names. This requires libiio v0.24
+    /// or later, and always returns `None` with older versions of the
+    /// library.
+    pub fn label(&self) -> Option<String> {
+        type GetLabelFn = unsafe extern "C" fn(*const ffi::iio_channel) -> *const c_char;
+
+        let pfn = lib_fn(b"iio_channel_get_label\0")?;
+        let pstr = unsafe {
+            let get_label: GetLabelFn = mem::transmute(pfn);
+            get_label(self.chan)
+        };
+        cstring_opt(pstr)
+    }
+
     /// Determines if this is an output channel.
     pub fn is_output(&self) -> bool {
         unsafe { ffi::iio_channel_is_output(self.chan) }
@@ -204,11 +400,14 @@ impl Channel {
 
     /// Gets the number of attributes for the channel
     pub fn num_attrs(&self) -> usize {
-        let n = unsafe { ffi::iio_channel_get_attrs_count(self.chan) };
-        n as usize
+        Attributes::num_attrs(self)
     }
 
     /// Determines if the channel has the specified attribute.
+    ///
+    /// This can be used to probe whether a channel supports an attribute,
+    /// like `raw`, before trying to read or write it, rather than treating
+    /// any error from the read as the attribute being absent.
     pub fn has_attr(&self, attr: &str) -> bool {
         let attr = cstring_or_bail_false!(attr);
         unsafe { !ffi::iio_channel_find_attr(self.chan, attr.as_ptr()).is_null() }
@@ -216,8 +415,7 @@ impl Channel {
 
     /// Gets the channel-specific attribute at the index
     pub fn get_attr(&self, idx: usize) -> Result<String> {
-        let pstr = unsafe { ffi::iio_channel_get_attr(self.chan, idx as c_uint) };
-        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+        Attributes::attr_name(self, idx)
     }
 
     /// Try to find the channel-specific attribute by name.
@@ -227,6 +425,31 @@ impl Channel {
         cstring_opt(pstr)
     }
 
+    /// Gets the sysfs directory that contains the channel's attribute files.
+    ///
+    /// This is the directory of the channel's device. It is only available
+    /// for local contexts, and returns `None` otherwise.
+    pub fn sysfs_path(&self) -> Option<PathBuf> {
+        if self.ctx.name() != "local" {
+            return None;
+        }
+        let id = self.device().id()?;
+        Some(PathBuf::from(SYSFS_IIO_DEVICES).join(id))
+    }
+
+    /// Gets the sysfs path of a channel-specific attribute's file.
+    ///
+    /// This allows the attribute to be read or written directly, bypassing
+    /// the library. Like [`Channel::sysfs_path()`], it is only available
+    /// for local contexts, and returns `None` otherwise, or if the channel
+    /// doesn't have the attribute.
+    pub fn attr_sysfs_path(&self, attr: &str) -> Option<PathBuf> {
+        let dir = self.sysfs_path()?;
+        let cattr = CString::new(attr).ok()?;
+        let pstr = unsafe { ffi::iio_channel_attr_get_filename(self.chan, cattr.as_ptr()) };
+        cstring_opt(pstr).map(|fname| dir.join(fname))
+    }
+
     /// Reads a channel-specific attribute
     ///
     /// `attr` The name of the attribute
@@ -239,35 +462,65 @@ impl Channel {
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_str(&self, attr: &str) -> Result<String> {
+        Attributes::attr_read(self, attr)
+    }
+
+    /// The same as [`Channel::attr_read_str()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_str_cstr(&self, attr: &CStr) -> Result<String> {
         let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
-        let attr = CString::new(attr)?;
         let ret = unsafe {
             ffi::iio_channel_attr_read(self.chan, attr.as_ptr(), buf.as_mut_ptr(), buf.len())
         };
         sys_result(ret as i32, ())?;
-        let s = unsafe {
-            CStr::from_ptr(buf.as_ptr())
-                .to_str()
-                .map_err(|_| Error::StringConversionError)?
-        };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()).to_str()? };
         Ok(s.into())
     }
 
     /// Reads a channel-specific attribute as a boolean
+    /// This accepts the various encodings that devices use, such as
+    /// "1"/"0", "true"/"false", and "Y"/"N".
+    ///
     /// `attr` The name of the attribute
     pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
-        let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_read_bool(self.chan, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        self.attr_read_bool_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Channel::attr_read_bool()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_bool_cstr(&self, attr: &CStr) -> Result<bool> {
+        let sval = self.attr_read_str_cstr(attr)?;
+        parse_bool(&sval)
     }
 
     /// Reads a channel-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_int(&self, attr: &str) -> Result<i64> {
+        self.attr_read_longlong(attr)
+    }
+
+    /// Reads a channel-specific attribute as a C `long long` (i64).
+    ///
+    /// This maps directly to `iio_channel_attr_read_longlong()` so that
+    /// integer-valued attributes, like `raw`, are read exactly without
+    /// going through a floating-point conversion.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_longlong(&self, attr: &str) -> Result<i64> {
+        self.attr_read_longlong_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Channel::attr_read_longlong()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_longlong_cstr(&self, attr: &CStr) -> Result<i64> {
         let mut val: c_longlong = 0;
-        let attr = CString::new(attr)?;
         let ret =
             unsafe { ffi::iio_channel_attr_read_longlong(self.chan, attr.as_ptr(), &mut val) };
         sys_result(ret, val as i64)
@@ -277,8 +530,15 @@ impl Channel {
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_float(&self, attr: &str) -> Result<f64> {
+        self.attr_read_float_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Channel::attr_read_float()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_float_cstr(&self, attr: &CStr) -> Result<f64> {
         let mut val: f64 = 0.0;
-        let attr = CString::new(attr)?;
         let ret = unsafe { ffi::iio_channel_attr_read_double(self.chan, attr.as_ptr(), &mut val) };
         sys_result(ret, val)
     }
@@ -316,24 +576,52 @@ impl Channel {
         sys_result(ret, map)
     }
 
+    /// Gets a map of the names and current values of all the
+    /// channel-specific attributes.
+    ///
+    /// Unlike [`Channel::attr_read_all()`], which gets all the values in a
+    /// single call to the library, this reads each attribute in turn, so it
+    /// works the same way with every backend.
+    pub fn attr_map(&self) -> Result<HashMap<String, String>> {
+        self.attrs()
+            .map(|name| {
+                let val = self.attr_read_str(&name)?;
+                Ok((name, val))
+            })
+            .collect()
+    }
+
     /// Writes a channel-specific attribute
     ///
+    /// On success, this returns the number of bytes written.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<usize> {
         let sval = attr_to_string(val)?;
         self.attr_write_str(attr, &sval)
     }
 
     /// Writes a channel-specific attribute as a string
     ///
+    /// On success, this returns the number of bytes written, which callers
+    /// can check to verify that the whole value was accepted.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<usize> {
+        Attributes::attr_write(self, attr, val)
+    }
+
+    /// The same as [`Channel::attr_write_str()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
-        let attr = CString::new(attr)?;
+    pub fn attr_write_str_cstr(&self, attr: &CStr, val: &str) -> Result<usize> {
         let sval = CString::new(val)?;
         let ret = unsafe { ffi::iio_channel_attr_write(self.chan, attr.as_ptr(), sval.as_ptr()) };
-        sys_result(ret as i32, ())
+        sys_result(ret as i32, ret as usize)
     }
 
     /// Writes a channel-specific attribute as a boolean
@@ -341,7 +629,15 @@ impl Channel {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_bool(&self, attr: &str, val: bool) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_bool_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Channel::attr_write_bool()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_bool_cstr(&self, attr: &CStr, val: bool) -> Result<()> {
         let ret = unsafe { ffi::iio_channel_attr_write_bool(self.chan, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
@@ -351,7 +647,27 @@ impl Channel {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_int(&self, attr: &str, val: i64) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_longlong(attr, val)
+    }
+
+    /// Writes a channel-specific attribute as a C `long long` (i64).
+    ///
+    /// This maps directly to `iio_channel_attr_write_longlong()` so that
+    /// integer-valued attributes, like `raw`, are written exactly without
+    /// any locale-dependent floating-point formatting.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_longlong(&self, attr: &str, val: i64) -> Result<()> {
+        self.attr_write_longlong_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Channel::attr_write_longlong()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_longlong_cstr(&self, attr: &CStr, val: i64) -> Result<()> {
         let ret = unsafe { ffi::iio_channel_attr_write_longlong(self.chan, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
@@ -361,11 +677,134 @@ impl Channel {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_float(&self, attr: &str, val: f64) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_float_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Channel::attr_write_float()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_float_cstr(&self, attr: &CStr, val: f64) -> Result<()> {
         let ret = unsafe { ffi::iio_channel_attr_write_double(self.chan, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
 
+    /// Reads the calibration values for the channel.
+    ///
+    /// Any of the calibration attributes that the channel doesn't have are
+    /// returned as `None`.
+    pub fn calibration(&self) -> Result<Calibration> {
+        let mut cal = Calibration::default();
+        if self.has_attr("calibbias") {
+            cal.bias = Some(self.attr_read_int("calibbias")?);
+        }
+        if self.has_attr("calibscale") {
+            cal.scale = Some(self.attr_read_float("calibscale")?);
+        }
+        Ok(cal)
+    }
+
+    /// Writes the calibration values for the channel.
+    ///
+    /// Only the values that are set (not `None`) are written.
+    pub fn set_calibration(&self, cal: &Calibration) -> Result<()> {
+        if let Some(bias) = cal.bias {
+            self.attr_write_int("calibbias", bias)?;
+        }
+        if let Some(scale) = cal.scale {
+            self.attr_write_float("calibscale", scale)?;
+        }
+        Ok(())
+    }
+
+    /// Gets the values that a channel-specific attribute supports.
+    ///
+    /// This reads and parses the companion `<attr>_available` attribute,
+    /// such as `scale_available` for the `scale` attribute.
+    ///
+    /// `attr` The name of the attribute
+    pub fn available_values(&self, attr: &str) -> Result<AvailableValues> {
+        self.attr_read_str(&format!("{}_available", attr))?.parse()
+    }
+
+    /// Reads the scale of the channel from its `scale` attribute.
+    ///
+    /// This is the factor to convert a raw value to physical units, after
+    /// the offset is applied. It returns `None` if the channel doesn't have
+    /// the attribute, in which case a scale of 1.0 should be assumed.
+    pub fn scale(&self) -> Result<Option<f64>> {
+        self.attr_read_float_opt("scale")
+    }
+
+    /// Reads the offset of the channel from its `offset` attribute.
+    ///
+    /// This is added to a raw value before it is scaled. It returns `None`
+    /// if the channel doesn't have the attribute, in which case an offset
+    /// of 0.0 should be assumed.
+    pub fn offset(&self) -> Result<Option<f64>> {
+        self.attr_read_float_opt("offset")
+    }
+
+    /// Converts a raw value to physical units, as `(raw + offset) * scale`.
+    ///
+    /// This uses the channel's [offset][Channel::offset()] and
+    /// [scale][Channel::scale()], if it has them.
+    pub fn to_physical(&self, raw: i64) -> Result<f64> {
+        Ok(PhysicalConversion::new(self)?.apply(raw as f64))
+    }
+
+    /// Converts a raw value to physical units, like
+    /// [`Channel::to_physical()`], rounding the result to the given number
+    /// of decimal places.
+    ///
+    /// This is for display and logging, which usually want a consistent
+    /// precision. An f64 has no more than 15 significant decimal digits, so
+    /// the value is left unrounded for more decimal places than that.
+    pub fn convert_with_precision(&self, raw: i64, decimals: u32) -> Result<f64> {
+        self.to_physical(raw).map(|val| round_to(val, decimals))
+    }
+
+    /// Determines if the channel has a processed value, in an `input`
+    /// attribute.
+    ///
+    /// A channel can provide a `raw` value, which must be converted to
+    /// physical units with the channel's offset and scale, or a processed
+    /// `input` value, which the driver has already converted, or both.
+    pub fn has_processed(&self) -> bool {
+        self.has_attr("input")
+    }
+
+    /// Reads the value of the channel in physical units.
+    ///
+    /// If the channel has a processed `input` attribute, this reads it,
+    /// since that value matches the driver's own conversion and
+    /// calibration. Otherwise it reads the `raw` attribute and converts it
+    /// with [`Channel::to_physical()`]. It's an error if the channel has
+    /// neither attribute.
+    pub fn read_processed(&self) -> Result<f64> {
+        if self.has_processed() {
+            self.attr_read_float("input")
+        }
+        else if self.has_attr("raw") {
+            self.to_physical(self.attr_read_int("raw")?)
+        }
+        else {
+            Err(Error::General(format!(
+                "Channel '{}' has no 'input' or 'raw' attribute",
+                self.id().unwrap_or_default()
+            )))
+        }
+    }
+
+    // Reads a floating-point attribute, if the channel has it.
+    fn attr_read_float_opt(&self, attr: &str) -> Result<Option<f64>> {
+        if !self.has_attr(attr) {
+            return Ok(None);
+        }
+        self.attr_read_float(attr).map(Some)
+    }
+
     /// Gets an iterator for the attributes of the channel
     pub fn attrs(&self) -> AttrIterator {
         AttrIterator { chan: self, idx: 0 }
@@ -417,6 +856,21 @@ impl Channel {
         }
     }
 
+    /// Gets the modifier of the channel, such as its axis.
+    ///
+    /// Channels without a modifier, or with one that isn't known to this
+    /// library, return [`Modifier::NoMod`].
+    pub fn modifier(&self) -> Modifier {
+        let n = unsafe { ffi::iio_channel_get_modifier(self.chan) };
+        if n <= ffi::iio_modifier_IIO_MOD_H2 {
+            // The modifier enum is contiguous up to H2.
+            unsafe { mem::transmute::<u32, Modifier>(n) }
+        }
+        else {
+            Modifier::NoMod
+        }
+    }
+
     /// Converts a single sample from the hardware format to the host format.
     ///
     /// To be properly converted, the value must be the same type as that of
@@ -466,6 +920,7 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
+        validate_sample_type::<T>(&self.data_format())?;
         if self.type_of() != Some(TypeId::of::<T>()) {
             return Err(Error::WrongDataType);
         }
@@ -494,6 +949,7 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
+        validate_sample_type::<T>(&self.data_format())?;
         if self.type_of() != Some(TypeId::of::<T>()) {
             return Err(Error::WrongDataType);
         }
@@ -523,6 +979,7 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
+        validate_sample_type::<T>(&self.data_format())?;
         if self.type_of() != Some(TypeId::of::<T>()) {
             return Err(Error::WrongDataType);
         }
@@ -543,6 +1000,7 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
+        validate_sample_type::<T>(&self.data_format())?;
         if self.type_of() != Some(TypeId::of::<T>()) {
             return Err(Error::WrongDataType);
         }
@@ -551,11 +1009,135 @@ impl Channel {
         let sz_in = data.len() * sz_item;
 
         let sz = unsafe {
-            ffi::iio_channel_write(self.chan, buf.buf, data.as_ptr() as *const c_void, sz_in)
+            ffi::iio_channel_write_raw(self.chan, buf.buf, data.as_ptr() as *const c_void, sz_in)
         };
 
         Ok(sz / sz_item)
     }
+
+    /// Demultiplex and convert the samples of the channel into a byte
+    /// slice, using the library's own deinterleaver.
+    ///
+    /// Unlike [`Channel::read()`], this doesn't check the sample type,
+    /// and it copies every repeat of each sample, so it can be used for
+    /// formats that the typed functions don't handle. The destination
+    /// should hold [`byte_length()`][DataFormat::byte_length()] bytes for
+    /// each sample.
+    /// Returns the number of bytes copied.
+    pub fn read_bytes(&self, buf: &Buffer, dst: &mut [u8]) -> usize {
+        unsafe {
+            ffi::iio_channel_read(
+                self.chan,
+                buf.buf,
+                dst.as_mut_ptr() as *mut c_void,
+                dst.len(),
+            )
+        }
+    }
+
+    /// Demultiplex the samples of the channel into a byte slice, without
+    /// converting them, using the library's own deinterleaver.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn read_raw_bytes(&self, buf: &Buffer, dst: &mut [u8]) -> usize {
+        unsafe {
+            ffi::iio_channel_read_raw(
+                self.chan,
+                buf.buf,
+                dst.as_mut_ptr() as *mut c_void,
+                dst.len(),
+            )
+        }
+    }
+
+    /// Convert and multiplex the samples of the channel from a byte slice,
+    /// using the library's own interleaver.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn write_bytes(&self, buf: &Buffer, src: &[u8]) -> usize {
+        unsafe {
+            ffi::iio_channel_write(self.chan, buf.buf, src.as_ptr() as *const c_void, src.len())
+        }
+    }
+
+    /// Multiplex the samples of the channel from a byte slice, without
+    /// converting them, using the library's own interleaver.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn write_raw_bytes(&self, buf: &Buffer, src: &[u8]) -> usize {
+        unsafe {
+            ffi::iio_channel_write_raw(self.chan, buf.buf, src.as_ptr() as *const c_void, src.len())
+        }
+    }
+}
+
+impl Attributes for Channel {
+    fn num_attrs(&self) -> usize {
+        let n = unsafe { ffi::iio_channel_get_attrs_count(self.chan) };
+        n as usize
+    }
+
+    fn attr_name(&self, idx: usize) -> Result<String> {
+        let pstr = unsafe { ffi::iio_channel_get_attr(self.chan, idx as c_uint) };
+        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+    }
+
+    fn attr_read(&self, name: &str) -> Result<String> {
+        self.attr_read_str_cstr(&CString::new(name)?)
+    }
+
+    fn attr_write(&self, name: &str, val: &str) -> Result<usize> {
+        self.attr_write_str_cstr(&CString::new(name)?, val)
+    }
+}
+
+/// The conversion of a channel's raw values to physical units, as
+/// `(raw + offset) * scale`.
+///
+/// This reads the channel's offset and scale once, so that it can be
+/// applied to many samples without further attribute reads.
+/// [`Channel::to_physical()`] is built on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalConversion {
+    /// The offset to add to a raw value before scaling
+    offset: f64,
+    /// The scale to convert an offset raw value to physical units
+    scale: f64,
+}
+
+impl PhysicalConversion {
+    /// Reads the conversion for the channel. A missing offset is taken as
+    /// 0.0 and a missing scale as 1.0.
+    pub fn new(chan: &Channel) -> Result<Self> {
+        Ok(Self {
+            offset: chan.offset()?.unwrap_or(0.0),
+            scale: chan.scale()?.unwrap_or(1.0),
+        })
+    }
+
+    /// Converts a raw value to physical units.
+    pub fn apply(&self, raw: f64) -> f64 {
+        (raw + self.offset) * self.scale
+    }
+}
+
+impl Default for PhysicalConversion {
+    /// The identity conversion, which leaves raw values unchanged.
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+// Rounds a value to the given number of decimal places.
+fn round_to(val: f64, decimals: u32) -> f64 {
+    if decimals > 15 {
+        return val;
+    }
+    let factor = 10f64.powi(decimals as i32);
+    (val * factor).round() / factor
 }
 
 /// Iterator over the attributes of a Channel
@@ -600,4 +1182,148 @@ mod tests {
         let chan = dev.get_channel(0);
         assert!(chan.is_ok());
     }
+
+    // Raw writes must not convert the samples. The dummy device's voltage0
+    // channel is a 13-bit value in 16 bits, so a converted write would mask
+    // off the upper bits.
+    #[test]
+    fn write_raw_unconverted() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        let trig = ctx.find_device("timer0").unwrap();
+
+        // Restore the previous trigger when done, after the buffer is dropped
+        struct RestoreTrigger(Device, Option<Device>);
+
+        impl Drop for RestoreTrigger {
+            fn drop(&mut self) {
+                let _ = match self.1 {
+                    Some(ref trig) => self.0.set_trigger(trig),
+                    None => self.0.remove_trigger(),
+                };
+            }
+        }
+
+        let _restore = RestoreTrigger(dev.clone(), dev.trigger().unwrap());
+        dev.set_trigger(&trig).unwrap();
+
+        let chan = dev.find_channel("voltage0", false).unwrap();
+        chan.enable();
+        let buf = dev.create_buffer(4, false).unwrap();
+
+        let n = chan.write_raw(&buf, &[0xFFFFu16; 4]).unwrap();
+        assert_eq!(n, 4);
+        let v = chan.read_raw::<u16>(&buf).unwrap();
+        assert!(v.iter().all(|&x| x == 0xFFFF));
+    }
+
+    // Decode a 12-bit sample, shifted by 4, in a 16-bit word.
+    #[test]
+    fn decode_sample() {
+        let mut fmt = DataFormat::new(ffi::iio_data_format {
+            length: 16,
+            bits: 12,
+            shift: 4,
+            is_signed: true,
+            is_fully_defined: false,
+            is_be: false,
+            with_scale: false,
+            scale: 1.0,
+            repeat: 1,
+        });
+        assert_eq!(fmt.decode_le(&[0xB0, 0xFF]), -5);
+        assert_eq!(fmt.decode_be(&[0xFF, 0xB0]), -5);
+        assert_eq!(fmt.decode_le(&[0x50, 0x00, 0xAA]), 5);
+        assert_eq!(fmt.decode(&[0xB0, 0xFF]), -5);
+
+        fmt.data_fmt.is_signed = false;
+        assert_eq!(fmt.decode_le(&[0xB0, 0xFF]), 0xFFB);
+
+        fmt.data_fmt.is_be = true;
+        assert_eq!(fmt.decode(&[0xFF, 0xB0]), 0xFFB);
+    }
+
+    // Values should be rounded to the requested precision.
+    #[test]
+    fn round_to_precision() {
+        assert_eq!(round_to(1.23456, 2), 1.23);
+        assert_eq!(round_to(-1.23556, 3), -1.236);
+        assert_eq!(round_to(2.5, 0), 3.0);
+        assert_eq!(round_to(0.1, 20), 0.1);
+    }
+
+    // An unsigned 10-bit sample in 16 bits must be zero-extended, with the
+    // unused upper bits cleared.
+    #[test]
+    fn decode_unsigned_sample() {
+        let mut fmt = DataFormat::new(ffi::iio_data_format {
+            length: 16,
+            bits: 10,
+            shift: 0,
+            is_signed: false,
+            is_fully_defined: false,
+            is_be: false,
+            with_scale: false,
+            scale: 1.0,
+            repeat: 1,
+        });
+        assert_eq!(fmt.decode_le(&[0xFF, 0xFF]), 0x3FF);
+        assert_eq!(fmt.decode_le(&[0x00, 0xFE]), 0x200);
+        assert_eq!(fmt.decode_be(&[0xFC, 0x01]), 0x001);
+        assert!(validate_sample_type::<u16>(&fmt).is_ok());
+
+        // The same bits, signed, are sign-extended
+        fmt.data_fmt.is_signed = true;
+        assert_eq!(fmt.decode_le(&[0xFF, 0xFF]), -1);
+        assert_eq!(fmt.decode_le(&[0x00, 0xFE]), -512);
+    }
+
+    // The sample type must match the storage size of the format.
+    #[test]
+    fn sample_type_size() {
+        let fmt = DataFormat::new(ffi::iio_data_format {
+            length: 16,
+            bits: 12,
+            shift: 0,
+            is_signed: true,
+            is_fully_defined: false,
+            is_be: false,
+            with_scale: false,
+            scale: 1.0,
+            repeat: 1,
+        });
+        assert!(validate_sample_type::<i16>(&fmt).is_ok());
+        assert!(validate_sample_type::<u16>(&fmt).is_ok());
+        assert!(matches!(
+            validate_sample_type::<i32>(&fmt),
+            Err(Error::SampleSizeMismatch {
+                type_size: 4,
+                sample_size: 2
+            })
+        ));
+    }
+
+    // The attribute map should have a value for each attribute.
+    #[test]
+    fn attr_map() {
+        let dev = Context::new().unwrap().get_device(0).unwrap();
+        let chan = dev.get_channel(0).unwrap();
+
+        let map = chan.attr_map().unwrap();
+        assert_eq!(map.len(), chan.num_attrs());
+        for name in chan.attrs() {
+            assert!(map.contains_key(&name));
+        }
+    }
+
+    // See that we can probe for channel attributes.
+    #[test]
+    fn has_attr() {
+        let dev = Context::new().unwrap().get_device(0).unwrap();
+        let chan = dev.get_channel(0).unwrap();
+
+        let attr = chan.get_attr(0).unwrap();
+        assert!(chan.has_attr(&attr));
+        assert!(!chan.has_attr("no_such_attribute"));
+    }
 }
@@ -10,10 +10,20 @@
 //! Industrial I/O Contexts.
 //!
 
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
 use std::ffi::CString;
+use std::fmt::Write;
+use std::fs;
+use std::net::IpAddr;
+use std::ops::{Deref, DerefMut};
 use std::os::raw::c_uint;
+use std::path::PathBuf;
 use std::ptr;
 use std::rc::Rc;
+use std::thread;
 use std::time::Duration;
 
 use nix::errno::Errno;
@@ -21,6 +31,16 @@ use nix::errno::Errno;
 use super::*;
 use crate::ffi;
 
+/// The name of the device used to create sysfs triggers
+const SYSFS_TRIGGER_DEV: &str = "iio_sysfs_trigger";
+
+/// The URI schemes of the library's backends, used to recognize a URI in
+/// [`Context::from_env()`] and the default context.
+const URI_SCHEMES: &[&str] = &["local:", "xml:", "ip:", "usb:", "serial:"];
+
+/// The default delay between attempts for [`Context::with_retry()`].
+const DFLT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
 /// An Industrial I/O Context
 ///
 /// Since the IIO library isn't thread safe, this object cannot be Send or
@@ -66,6 +86,13 @@ pub enum Backend<'a> {
     /// - "plutosdr.local" to connect to host with given hostname, **or**
     /// - "" for automatic discovery
     ///
+    /// The library enables TCP keepalive on the socket of a network
+    /// context, but doesn't expose the socket, so the keepalive timing
+    /// can't be adjusted. To keep a long-lived connection robust, set an
+    /// I/O timeout with [`Context::set_timeout()`], retry operations that
+    /// fail with a [transient error][Error::is_transient()], and recover
+    /// from a dropped connection with [`Context::reconnect()`].
+    ///
     /// [IIO Daemon]: https://github.com/analogdevicesinc/libiio/tree/master/iiod
     Network(&'a str),
     /// USB Backend, creates a context through a USB connection.
@@ -109,17 +136,52 @@ pub enum Backend<'a> {
 pub struct InnerContext {
     /// Pointer to a libiio Context object
     pub(crate) ctx: *mut ffi::iio_context,
+    /// The URI used to create the context, if known.
+    uri: Option<String>,
+    /// The last I/O timeout set on the context, if any.
+    /// The library can't be queried for this, so we cache it here.
+    timeout: Cell<Option<Duration>>,
+    /// Callbacks to run when the library context is destroyed.
+    on_destroy: DestroyCallbacks,
+}
+
+/// The callbacks to run when a context is destroyed.
+/// These must be Send, since the inner context can be sent to another
+/// thread.
+#[derive(Default)]
+struct DestroyCallbacks(RefCell<Vec<Box<dyn FnOnce() + Send>>>);
+
+impl fmt::Debug for DestroyCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DestroyCallbacks({})", self.0.borrow().len())
+    }
 }
 
 impl InnerContext {
     /// Tries to create the inner context from a C pointer.
     /// This should be called _right after_ creating the C context as it
     /// will use the last error on failure.
-    fn new(ctx: *mut ffi::iio_context) -> Result<Self> {
-        match ctx.is_null() {
-            true => Err(Error::from(Errno::last())),
-            false => Ok(Self { ctx }),
+    ///
+    /// `uri` The URI used to create the context, if known. This is kept
+    ///     for diagnostics, and reported in the error on failure.
+    fn new(ctx: *mut ffi::iio_context, uri: Option<String>) -> Result<Self> {
+        if ctx.is_null() {
+            let errno = Errno::last();
+            return Err(match uri {
+                Some(uri) => Error::ContextCreation {
+                    uri,
+                    errno,
+                    msg: strerror(errno),
+                },
+                None => errno.into(),
+            });
         }
+        Ok(Self {
+            ctx,
+            uri,
+            timeout: Cell::new(None),
+            on_destroy: DestroyCallbacks::default(),
+        })
     }
 
     /// Create a clone of the underlying context that can be used in another thread.
@@ -128,14 +190,23 @@ impl InnerContext {
         if ctx.is_null() {
             Err(Error::from(Errno::last()))?;
         }
-        Ok(InnerContext { ctx })
+        Ok(InnerContext {
+            ctx,
+            uri: self.uri.clone(),
+            timeout: Cell::new(None),
+            on_destroy: DestroyCallbacks::default(),
+        })
     }
 }
 
 impl Drop for InnerContext {
-    /// Dropping destroys the underlying context.
+    /// Dropping destroys the underlying context, then runs any callbacks
+    /// that were registered with [`Context::on_destroy()`].
     fn drop(&mut self) {
         unsafe { ffi::iio_context_destroy(self.ctx) };
+        for f in self.on_destroy.0.get_mut().drain(..) {
+            f();
+        }
     }
 }
 
@@ -152,8 +223,32 @@ impl Context {
     /// runs. If set to an empty string, the server will be discovered using
     /// ZeroConf. If the environment variable is not set, a local context
     /// will be created instead.
+    ///
+    /// The URI of the context that was selected can be retrieved with
+    /// [`Context::uri()`]. See [`Context::from_env()`].
     pub fn new() -> Result<Context> {
-        Self::from_ptr(unsafe { ffi::iio_create_default_context() })
+        Self::with_backend(Backend::Default)
+    }
+
+    /// Creates a context selected by the named environment variable.
+    ///
+    /// This follows the same rules as the default context, but with any
+    /// environment variable:
+    ///
+    /// - If the variable holds a URI with one of the library's schemes,
+    ///   like "ip:192.168.2.1" or "usb:", a context is created for that
+    ///   URI.
+    /// - Otherwise, if it is set, it is taken as a hostname or IP address,
+    ///   including an IPv6 address, and a network context is created for
+    ///   that host. If it is an empty string, the server will be
+    ///   discovered using ZeroConf.
+    /// - If the variable is not set, a local context is created.
+    pub fn from_env(var: &str) -> Result<Context> {
+        match env::var(var) {
+            Ok(val) if has_uri_scheme(&val) => Self::from_uri(&val),
+            Ok(host) => Self::with_backend(Backend::Network(&host)),
+            Err(_) => Self::from_uri("local:"),
+        }
     }
 
     /// Create an IIO Context.
@@ -195,45 +290,84 @@ impl Context {
     /// let ctx = iio::Context::with_backend(iio::Backend::Uri("ip:192.168.2.1"));
     /// ```
     pub fn with_backend(be: Backend) -> Result<Context> {
-        Self::from_ptr(unsafe {
+        let (ctx, uri) = unsafe {
             match be {
-                Backend::Default => ffi::iio_create_default_context(),
+                Backend::Default => (ffi::iio_create_default_context(), Some(default_uri())),
                 Backend::Xml(name) => {
+                    let uri = format!("xml:{}", name);
                     let name = CString::new(name)?;
-                    ffi::iio_create_xml_context(name.as_ptr())
+                    (ffi::iio_create_xml_context(name.as_ptr()), Some(uri))
                 }
                 Backend::XmlMem(xml) => {
                     let n = xml.len();
                     let xml = CString::new(xml)?;
-                    ffi::iio_create_xml_context_mem(xml.as_ptr(), n)
+                    (ffi::iio_create_xml_context_mem(xml.as_ptr(), n), None)
                 }
                 Backend::Network(host) => {
+                    let uri = format!("ip:{}", host);
                     let host = CString::new(host)?;
-                    ffi::iio_create_network_context(host.as_ptr())
+                    (ffi::iio_create_network_context(host.as_ptr()), Some(uri))
                 }
                 Backend::Usb(device) => {
-                    let uri = CString::new(format!("usb:{}", device))?;
-                    ffi::iio_create_context_from_uri(uri.as_ptr())
+                    let uri = format!("usb:{}", device);
+                    let curi = CString::new(uri.as_str())?;
+                    (ffi::iio_create_context_from_uri(curi.as_ptr()), Some(uri))
                 }
                 Backend::Serial(tty) => {
-                    let uri = CString::new(format!("serial:{}", tty))?;
-                    ffi::iio_create_context_from_uri(uri.as_ptr())
+                    let uri = format!("serial:{}", tty);
+                    let curi = CString::new(uri.as_str())?;
+                    (ffi::iio_create_context_from_uri(curi.as_ptr()), Some(uri))
                 }
                 Backend::Uri(uri) => {
-                    let uri = CString::new(uri)?;
-                    ffi::iio_create_context_from_uri(uri.as_ptr())
+                    let curi = CString::new(uri)?;
+                    (
+                        ffi::iio_create_context_from_uri(curi.as_ptr()),
+                        Some(uri.to_string()),
+                    )
                 }
                 #[cfg(target_os = "linux")]
-                Backend::Local => ffi::iio_create_local_context(),
+                Backend::Local => (ffi::iio_create_local_context(), Some("local:".to_string())),
             }
-        })
+        };
+
+        Self::from_ptr(ctx, uri)
     }
 
     /// Creates a context specified by the `uri`.
+    ///
+    /// On failure, this returns an [`Error::ContextCreation`] holding the
+    /// URI and the system error, so that a bad hostname can be told apart
+    /// from a daemon that isn't running.
+    ///
+    /// For a network (`ip:`) URI, see [`Backend::Network`] for how to keep
+    /// a long-lived connection robust.
     pub fn from_uri(uri: &str) -> Result<Self> {
         Self::with_backend(Backend::Uri(uri))
     }
 
+    /// Scans the USB and network backends, and connects to the first
+    /// context that is found.
+    ///
+    /// This is a simple way for a tool that works with a single device to
+    /// connect to whatever is available, without needing a URI. If a
+    /// context is found but can't be created, the next one is tried. It's
+    /// an error if no contexts are found. Note that the network scan can
+    /// take a few seconds.
+    pub fn auto_connect() -> Result<Self> {
+        let infos = ScanContext::new(&["usb", "ip"])?.contexts()?;
+        let mut last_err = None;
+
+        for info in infos {
+            match Self::from_uri(&info.uri) {
+                Ok(ctx) => return Ok(ctx),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            Error::General("No IIO contexts were found on the USB or network backends".into())
+        }))
+    }
+
     /// Creates a context from an existing "inner" object.
     pub fn from_inner(inner: InnerContext) -> Self {
         Self {
@@ -241,9 +375,10 @@ impl Context {
         }
     }
 
-    /// Creates a Rust Context object from a C context pointer.
-    fn from_ptr(ctx: *mut ffi::iio_context) -> Result<Self> {
-        let inner = InnerContext::new(ctx)?;
+    /// Creates a Rust Context object from a C context pointer and the URI
+    /// that was used to create it, if known.
+    fn from_ptr(ctx: *mut ffi::iio_context, uri: Option<String>) -> Result<Self> {
+        let inner = InnerContext::new(ctx, uri)?;
         Ok(Self::from_inner(inner))
     }
 
@@ -254,6 +389,21 @@ impl Context {
         self.inner.try_clone()
     }
 
+    /// Registers a callback to run when the underlying library context is
+    /// destroyed.
+    ///
+    /// The context is shared by all of its clones, and by the devices,
+    /// channels, and buffers created from it, so it's only destroyed when
+    /// the last of these is dropped. This allows resource-tracking code to
+    /// know when that actually happens. A new context made from
+    /// [`Context::try_clone_inner()`] doesn't inherit the callbacks.
+    pub fn on_destroy<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner.on_destroy.0.borrow_mut().push(Box::new(f));
+    }
+
     /// Get the name of the context.
     /// This should be "local", "xml", or "network" depending on how the context was created.
     pub fn name(&self) -> String {
@@ -261,12 +411,37 @@ impl Context {
         cstring_opt(pstr).unwrap_or_default()
     }
 
+    /// Gets the URI that was used to create the context, if known.
+    ///
+    /// This is useful to log which endpoint a network context is talking
+    /// to. For the default context, this is the URI of the backend that
+    /// was selected. It is `None` for contexts created from in-memory XML.
+    pub fn uri(&self) -> Option<String> {
+        self.inner.uri.clone()
+    }
+
     /// Get a description of the context
     pub fn description(&self) -> String {
         let pstr = unsafe { ffi::iio_context_get_description(self.inner.ctx) };
         cstring_opt(pstr).unwrap_or_default()
     }
 
+    /// Parses the description of the context into its fields.
+    ///
+    /// The description is backend-specific, but a few forms are known:
+    ///
+    /// - A local context is described by the `uname` of the system, which
+    ///   is parsed into the `sysname`, `hostname`, `kernel`,
+    ///   `kernel_build`, and `machine` fields.
+    /// - A network context is described by the IP address of the server,
+    ///   in the `ip` field, followed by the description of the server's
+    ///   local context, which is parsed as above.
+    ///
+    /// Any other description is returned whole, in a `raw` field.
+    pub fn description_fields(&self) -> HashMap<String, String> {
+        parse_description(&self.name(), &self.description())
+    }
+
     /// Get the version of the backend in use
     pub fn version(&self) -> Version {
         let mut major: c_uint = 0;
@@ -294,6 +469,48 @@ impl Context {
         }
     }
 
+    /// Checks the version of the local library against that of the
+    /// context, and returns a warning for each mismatch.
+    ///
+    /// With a network context, the version of the remote IIO daemon can
+    /// differ from that of the local library, and this skew is a common
+    /// cause of subtle misbehavior. A difference in the major version can
+    /// break the protocol, while one in the minor version might mean that
+    /// some features are missing on one side. An empty list means that
+    /// the versions are compatible.
+    pub fn compatibility_check(&self) -> Vec<String> {
+        let lib = library_version();
+        let ctx = self.version();
+        let mut warnings = Vec::new();
+
+        if lib.major != ctx.major {
+            warnings.push(format!(
+                "The library major version {} differs from the context's {}, \
+                 so the protocol may be incompatible",
+                lib.major, ctx.major
+            ));
+        }
+        else if lib.minor != ctx.minor {
+            warnings.push(format!(
+                "The library version {}.{} differs from the context's {}.{}, \
+                 so some features may be unavailable",
+                lib.major, lib.minor, ctx.major, ctx.minor
+            ));
+        }
+        warnings
+    }
+
+    /// Gets the version reported by a specific backend of the context.
+    ///
+    /// The version of libiio used by this crate has no per-backend version
+    /// query, so this returns the overall [context version][Context::version()]
+    /// for any backend. That is still useful for diagnosing mismatches, since
+    /// for a network context it is the version of the remote server, which
+    /// can be compared to the local [`library_version()`].
+    pub fn backend_version(&self, _backend: &str) -> Version {
+        self.version()
+    }
+
     /// Obtain the XML representation of the context.
     pub fn xml(&self) -> String {
         let pstr = unsafe { ffi::iio_context_get_xml(self.inner.ctx) };
@@ -307,8 +524,7 @@ impl Context {
 
     /// Gets the number of context-specific attributes
     pub fn num_attrs(&self) -> usize {
-        let n = unsafe { ffi::iio_context_get_attrs_count(self.inner.ctx) };
-        n as usize
+        Attributes::num_attrs(self)
     }
 
     /// Gets the name and value of the context-specific attributes.
@@ -333,6 +549,33 @@ impl Context {
         Ok((name.unwrap(), val.unwrap()))
     }
 
+    /// Reads the value of a context-specific attribute by name.
+    ///
+    /// `name` The name of the attribute
+    pub fn attr_read_str(&self, name: &str) -> Result<String> {
+        Attributes::attr_read(self, name)
+    }
+
+    /// Reads a context-specific attribute as an integer (i64)
+    ///
+    /// `name` The name of the attribute
+    pub fn attr_read_int(&self, name: &str) -> Result<i64> {
+        let sval = self.attr_read_str(name)?;
+        sval.trim()
+            .parse()
+            .map_err(|_| Error::StringConversionError)
+    }
+
+    /// Reads a context-specific attribute as a floating-point (f64) number
+    ///
+    /// `name` The name of the attribute
+    pub fn attr_read_float(&self, name: &str) -> Result<f64> {
+        let sval = self.attr_read_str(name)?;
+        sval.trim()
+            .parse()
+            .map_err(|_| Error::StringConversionError)
+    }
+
     /// Gets an iterator for the attributes in the context
     pub fn attributes(&self) -> AttrIterator {
         AttrIterator { ctx: self, idx: 0 }
@@ -342,9 +585,14 @@ impl Context {
     ///
     /// `timeout` The timeout. A value of zero specifies that no timeout
     ///     should be used.
-    pub fn set_timeout(&self, timeout: Duration) -> Result<()> {
+    ///
+    /// On success this returns the previous timeout, if one was set. See
+    /// [`Context::timeout()`].
+    pub fn set_timeout(&self, timeout: Duration) -> Result<Option<Duration>> {
+        let prev = self.timeout();
         let ms: u64 = 1000 * timeout.as_secs() + u64::from(timeout.subsec_millis());
-        self.set_timeout_ms(ms)
+        self.set_timeout_ms(ms)?;
+        Ok(prev)
     }
 
     /// Sets the timeout for I/O operations, in milliseconds
@@ -353,7 +601,126 @@ impl Context {
     ///     timeout should be used.
     pub fn set_timeout_ms(&self, ms: u64) -> Result<()> {
         let ret = unsafe { ffi::iio_context_set_timeout(self.inner.ctx, ms as c_uint) };
-        sys_result(ret, ())
+        sys_result(ret, ())?;
+        self.inner.timeout.set(Some(Duration::from_millis(ms)));
+        Ok(())
+    }
+
+    /// Reconnects a network context, by creating a new connection from the
+    /// URI that was used to create the context.
+    ///
+    /// This lets a long-running service recover from a dropped connection
+    /// while keeping the same `Context` value. The I/O timeout, if one was
+    /// set, is applied to the new connection.
+    ///
+    /// Any [`Device`], [`Channel`], or [`Buffer`] handles obtained from the
+    /// context before the reconnect still refer to the old connection, and
+    /// must be re-acquired from the context. They remain safe to use, or
+    /// drop, but operations on them will likely fail. Clones of the
+    /// context made before the reconnect also keep the old connection.
+    ///
+    /// The callbacks registered with [`Context::on_destroy()`] are moved to
+    /// the new connection, and run when it's destroyed, rather than when
+    /// the old one is.
+    pub fn reconnect(&mut self) -> Result<()> {
+        if self.name() != "network" {
+            return Err(Error::General(
+                "Only a network context can be reconnected".into(),
+            ));
+        }
+        let uri = self
+            .uri()
+            .ok_or_else(|| Error::General("The URI of the context is unknown".into()))?;
+
+        let ctx = Self::from_uri(&uri)?;
+        if let Some(timeout) = self.timeout() {
+            ctx.set_timeout(timeout)?;
+        }
+        ctx.inner
+            .on_destroy
+            .0
+            .borrow_mut()
+            .append(&mut self.inner.on_destroy.0.borrow_mut());
+        self.inner = ctx.inner;
+        Ok(())
+    }
+
+    /// Gets the timeout for I/O operations.
+    ///
+    /// The library can't report the timeout, so this is the last value
+    /// set through this crate. It returns `None` if the timeout was never
+    /// set, meaning the backend's default is in effect.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout.get()
+    }
+
+    /// Temporarily sets the timeout for I/O operations.
+    ///
+    /// This returns a guard that restores the previous timeout when it goes
+    /// out of scope. The guard dereferences to the context, so it can be
+    /// used for the operations that need the temporary timeout.
+    ///
+    /// The backend's default timeout can't be queried from the library, so
+    /// this is an error if the timeout was never set explicitly, as there
+    /// would be no previous value to restore. Call
+    /// [`Context::set_timeout()`] first to set a known timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use industrial_io as iio;
+    /// use std::time::Duration;
+    ///
+    /// let mut ctx = iio::Context::new().unwrap();
+    /// ctx.set_timeout(Duration::from_secs(5)).unwrap();
+    /// {
+    ///     let ctx = ctx.with_timeout(Duration::from_secs(30)).unwrap();
+    ///     // ...perform a slow operation...
+    /// }
+    /// ```
+    pub fn with_timeout(&mut self, timeout: Duration) -> Result<TimeoutGuard<'_>> {
+        let prev = self.timeout().ok_or_else(|| {
+            Error::General("No previous timeout to restore. Set one explicitly first".into())
+        })?;
+        self.set_timeout(timeout)?;
+        Ok(TimeoutGuard { ctx: self, prev })
+    }
+
+    /// Runs an operation, retrying it if it fails with a transient error.
+    ///
+    /// Network connections to an IIO daemon occasionally time out or drop,
+    /// and this retries the operation up to a total of `attempts` times if
+    /// it fails with one of the errors listed in [`Error::is_transient()`].
+    /// Any other error is returned immediately.
+    ///
+    /// This waits a little longer before each successive attempt, starting
+    /// at 100ms. See [`Context::with_retry_backoff()`] to set the delay.
+    pub fn with_retry<F, T>(&self, attempts: u32, f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        self.with_retry_backoff(attempts, DFLT_RETRY_BACKOFF, f)
+    }
+
+    /// Runs an operation, retrying it if it fails with a transient error,
+    /// using the specified backoff.
+    ///
+    /// This is the same as [`Context::with_retry()`], but the delay before
+    /// the n'th retry is `n * backoff`.
+    pub fn with_retry_backoff<F, T>(&self, attempts: u32, backoff: Duration, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut n = 1;
+        loop {
+            match f() {
+                Err(err) if err.is_transient() && n < attempts => {
+                    thread::sleep(backoff * n);
+                    n += 1;
+                }
+                res => return res,
+            }
+        }
     }
 
     /// Get the number of devices in the context
@@ -389,15 +756,299 @@ impl Context {
         }
     }
 
+    /// Reads a single value from an input channel, in physical units.
+    ///
+    /// This finds the device and its input channel, reads the channel's
+    /// `raw` attribute, and converts it as `(raw + offset) * scale`, using
+    /// the channel's offset and scale, if it has them. It's meant for
+    /// scripts and quick diagnostics.
+    ///
+    /// `device` The name or ID of the device
+    /// `channel` The name or ID of the input channel
+    pub fn quick_read(&self, device: &str, channel: &str) -> Result<f64> {
+        let dev = self
+            .find_device(device)
+            .ok_or_else(|| Error::General(format!("Device '{}' not found", device)))?;
+        let chan = dev.find_channel(channel, false).ok_or_else(|| {
+            Error::General(format!(
+                "Input channel '{}' not found in device '{}'",
+                channel, device
+            ))
+        })?;
+        if !chan.has_attr("raw") {
+            return Err(Error::General(format!(
+                "Channel '{}' of device '{}' has no 'raw' attribute",
+                channel, device
+            )));
+        }
+        chan.to_physical(chan.attr_read_int("raw")?)
+    }
+
     /// Gets an iterator for all the devices in the context.
     pub fn devices(&self) -> DeviceIterator {
         DeviceIterator { ctx: self, idx: 0 }
     }
 
+    /// Gets the ID and name of each of the devices in the context.
+    ///
+    /// This is a lightweight listing of what's available, for CLIs and
+    /// logging, which reads the strings directly from the library rather
+    /// than creating a [`Device`] for each one.
+    pub fn device_summaries(&self) -> Vec<(String, Option<String>)> {
+        (0..self.num_devices())
+            .filter_map(|idx| unsafe {
+                let dev = ffi::iio_context_get_device(self.inner.ctx, idx as c_uint);
+                if dev.is_null() {
+                    return None;
+                }
+                let id = cstring_opt(ffi::iio_device_get_id(dev)).unwrap_or_default();
+                Some((id, cstring_opt(ffi::iio_device_get_name(dev))))
+            })
+            .collect()
+    }
+
+    /// Gets an iterator for the trigger devices in the context.
+    pub fn triggers(&self) -> impl Iterator<Item = Device> + '_ {
+        self.devices().filter(|dev| dev.is_trigger())
+    }
+
+    /// Creates a sysfs software trigger with the specified ID.
+    ///
+    /// This writes the ID to the `add_trigger` attribute of the
+    /// `iio_sysfs_trigger` device, which requires the `iio-trig-sysfs`
+    /// kernel module to be loaded, and usually root privileges.
+    ///
+    /// The devices in a context are fixed when it is created, so the new
+    /// trigger, named `sysfstrig<id>`, is looked up in a fresh clone of
+    /// this context. The returned device holds a reference to that clone.
+    pub fn create_sysfs_trigger(&self, id: u32) -> Result<Device> {
+        self.sysfs_trigger_dev()?
+            .attr_write_int("add_trigger", i64::from(id))?;
+
+        let name = format!("sysfstrig{}", id);
+        let ctx = Context::from_inner(self.try_clone_inner()?);
+        ctx.find_device(&name).ok_or_else(|| {
+            Error::General(format!(
+                "The trigger '{}' was not found after creating it",
+                name
+            ))
+        })
+    }
+
+    /// Removes the sysfs software trigger with the specified ID.
+    ///
+    /// This writes the ID to the `remove_trigger` attribute of the
+    /// `iio_sysfs_trigger` device. The trigger should not be in use by any
+    /// device when it is removed.
+    pub fn remove_sysfs_trigger(&self, id: u32) -> Result<()> {
+        self.sysfs_trigger_dev()?
+            .attr_write_int("remove_trigger", i64::from(id))
+    }
+
+    // Gets the device used to add and remove sysfs triggers.
+    fn sysfs_trigger_dev(&self) -> Result<Device> {
+        self.find_device(SYSFS_TRIGGER_DEV).ok_or_else(|| {
+            Error::General(format!(
+                "The '{}' device isn't available. Is the iio-trig-sysfs module loaded?",
+                SYSFS_TRIGGER_DEV
+            ))
+        })
+    }
+
+    /// Creates an `hrtimer` software trigger and sets its rate.
+    ///
+    /// High-resolution timer triggers are created through configfs, by
+    /// making the directory `<configfs>/iio/triggers/hrtimer/<name>`. This
+    /// requires configfs to be mounted (typically at `/config` or
+    /// `/sys/kernel/config`), the `iio-trig-hrtimer` kernel module to be
+    /// loaded, and usually root privileges. It only works for a local
+    /// context. If the trigger already exists, it is reused. If the trigger
+    /// is created, but can't then be set up, it is removed again.
+    ///
+    /// As with [`Context::create_sysfs_trigger()`], the new trigger is
+    /// looked up in a fresh clone of this context.
+    ///
+    /// `name` The name of the trigger
+    /// `hz` The sampling frequency of the trigger
+    pub fn create_hrtimer_trigger(&self, name: &str, hz: f64) -> Result<Device> {
+        let path = self.hrtimer_path()?.join(name);
+        let created = !path.is_dir();
+        if created {
+            fs::create_dir(&path)?;
+        }
+
+        let res = (|| {
+            let ctx = Context::from_inner(self.try_clone_inner()?);
+            let trig = ctx.find_device(name).ok_or_else(|| {
+                Error::General(format!(
+                    "The trigger '{}' was not found after creating it",
+                    name
+                ))
+            })?;
+            trig.attr_write_float("sampling_frequency", hz)?;
+            Ok(trig)
+        })();
+
+        // Don't leave behind a trigger that this call created on failure
+        if res.is_err() && created {
+            let _ = fs::remove_dir(&path);
+        }
+        res
+    }
+
+    /// Removes an `hrtimer` software trigger.
+    ///
+    /// This removes the trigger's configfs directory. The trigger should
+    /// not be in use by any device when it is removed.
+    pub fn remove_hrtimer_trigger(&self, name: &str) -> Result<()> {
+        fs::remove_dir(self.hrtimer_path()?.join(name))?;
+        Ok(())
+    }
+
+    // Gets the configfs directory used to create hrtimer triggers.
+    fn hrtimer_path(&self) -> Result<PathBuf> {
+        if self.name() != "local" {
+            return Err(Error::General(
+                "Triggers can only be created in a local context".into(),
+            ));
+        }
+        let mounts = fs::read_to_string("/proc/mounts")?;
+        let configfs = mounts
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|fields| fields.len() > 2 && fields[2] == "configfs")
+            .map(|fields| PathBuf::from(fields[1]))
+            .ok_or_else(|| Error::General("configfs is not mounted".into()))?;
+
+        let path = configfs.join("iio/triggers/hrtimer");
+        if !path.is_dir() {
+            return Err(Error::General(format!(
+                "'{}' doesn't exist. Is the iio-trig-hrtimer module loaded?",
+                path.display()
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Creates a human-readable description of the whole context.
+    ///
+    /// This is similar to the output of the `iio_info` utility, and is
+    /// useful as a built-in diagnostics command. It lists the context's
+    /// description and attributes, then each device, its attributes, and
+    /// its channels with their attributes, as an indented tree. An
+    /// attribute that can't be read is shown with the error.
+    pub fn dump(&self) -> String {
+        fn attr_line<T>(out: &mut String, indent: usize, name: &str, val: Result<T>)
+        where
+            T: fmt::Display,
+        {
+            let _ = match val {
+                Ok(val) => writeln!(out, "{:indent$}{}: {}", "", name, val, indent = indent),
+                Err(err) => writeln!(out, "{:indent$}{}: <{}>", "", name, err, indent = indent),
+            };
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "Context: {}", self.name());
+        let _ = writeln!(out, "  Description: {}", self.description());
+
+        let _ = writeln!(out, "  Attributes: {}", self.num_attrs());
+        for (name, val) in self.attributes() {
+            let _ = writeln!(out, "    {}: {}", name, val);
+        }
+
+        let _ = writeln!(out, "  Devices: {}", self.num_devices());
+        for dev in self.devices() {
+            let _ = writeln!(
+                out,
+                "    {}: {}",
+                dev.id().unwrap_or_default(),
+                dev.name().unwrap_or_default()
+            );
+
+            let _ = writeln!(out, "      Attributes: {}", dev.num_attrs());
+            for name in dev.attributes() {
+                attr_line(&mut out, 8, &name, dev.attr_read_str(&name));
+            }
+
+            let _ = writeln!(out, "      Channels: {}", dev.num_channels());
+            for chan in dev.channels() {
+                let dir = if chan.is_output() { "output" } else { "input" };
+                let _ = writeln!(
+                    out,
+                    "        {} ({}, {:?})",
+                    chan.id().unwrap_or_default(),
+                    dir,
+                    chan.channel_type()
+                );
+                for name in chan.attrs() {
+                    attr_line(&mut out, 10, &name, chan.attr_read_str(&name));
+                }
+            }
+        }
+        out
+    }
+
     /// Destroy the context
     ///
     /// This consumes the context to destroy the instance.
     pub fn destroy(self) {}
+
+    /// Disconnects the context, closing any connection immediately.
+    ///
+    /// Unlike [`Context::destroy()`], which only drops this reference to
+    /// the shared context, this guarantees that the underlying context is
+    /// destroyed, and for a network backend, that its socket is closed.
+    ///
+    /// This must be the only reference to the context, so any clones of
+    /// it, and any devices, channels, or buffers created from it must be
+    /// dropped first. Otherwise an error is returned and the context is
+    /// left intact.
+    pub fn disconnect(self) -> Result<()> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => {
+                drop(inner);
+                Ok(())
+            }
+            Err(_) => Err(Error::General(
+                "The context is still in use and can't be disconnected".into(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<&str> for Context {
+    type Error = Error;
+
+    /// Creates a context from a URI, like [`Context::from_uri()`].
+    fn try_from(uri: &str) -> Result<Self> {
+        Self::from_uri(uri)
+    }
+}
+
+impl Attributes for Context {
+    fn num_attrs(&self) -> usize {
+        let n = unsafe { ffi::iio_context_get_attrs_count(self.inner.ctx) };
+        n as usize
+    }
+
+    fn attr_name(&self, idx: usize) -> Result<String> {
+        self.get_attr(idx).map(|(name, _)| name)
+    }
+
+    fn attr_read(&self, name: &str) -> Result<String> {
+        let cname = CString::new(name)?;
+        let pval = unsafe { ffi::iio_context_get_attr_value(self.inner.ctx, cname.as_ptr()) };
+        if pval.is_null() {
+            return Err(Errno::ENOENT.into());
+        }
+        cstring_opt(pval).ok_or(Error::StringConversionError)
+    }
+
+    /// Context attributes are read-only, so this always fails.
+    fn attr_write(&self, _name: &str, _val: &str) -> Result<usize> {
+        Err(Errno::EPERM.into())
+    }
 }
 
 impl PartialEq for Context {
@@ -408,6 +1059,178 @@ impl PartialEq for Context {
     }
 }
 
+// Determines if a string starts with the scheme of one of the backends.
+fn has_uri_scheme(s: &str) -> bool {
+    URI_SCHEMES.iter().any(|scheme| s.starts_with(scheme))
+}
+
+// Gets the URI of the context that the library's default context function
+// creates. Like the library, this uses the IIOD_REMOTE environment
+// variable if it's set, either as a full URI or as the host for the
+// network backend, and the local backend otherwise.
+fn default_uri() -> String {
+    match env::var("IIOD_REMOTE") {
+        Ok(val) if has_uri_scheme(&val) => val,
+        Ok(host) => format!("ip:{}", host),
+        Err(_) => "local:".to_string(),
+    }
+}
+
+// Parses a context description for `Context::description_fields()`.
+fn parse_description(name: &str, desc: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let parsed = match name {
+        "local" => parse_uname(desc, &mut map),
+        "network" => {
+            let mut parts = desc.splitn(2, ' ');
+            match (parts.next().map(str::parse::<IpAddr>), parts.next()) {
+                (Some(Ok(ip)), Some(rest)) => {
+                    map.insert("ip".to_string(), ip.to_string());
+                    parse_uname(rest, &mut map)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+
+    if !parsed {
+        map.clear();
+        map.insert("raw".to_string(), desc.to_string());
+    }
+    map
+}
+
+// Parses the fields of a `uname -a` string, which are separated by spaces,
+// although the kernel build version usually has spaces itself.
+fn parse_uname(s: &str, map: &mut HashMap<String, String>) -> bool {
+    let fields: Vec<&str> = s.split_whitespace().collect();
+    if fields.len() < 5 {
+        return false;
+    }
+    let n = fields.len();
+    map.insert("sysname".to_string(), fields[0].to_string());
+    map.insert("hostname".to_string(), fields[1].to_string());
+    map.insert("kernel".to_string(), fields[2].to_string());
+    map.insert("kernel_build".to_string(), fields[3..n - 1].join(" "));
+    map.insert("machine".to_string(), fields[n - 1].to_string());
+    true
+}
+
+/// A guard that restores the previous I/O timeout of a context when it
+/// goes out of scope.
+///
+/// This is created by [`Context::with_timeout()`].
+#[derive(Debug)]
+pub struct TimeoutGuard<'a> {
+    /// The context with the temporary timeout
+    ctx: &'a mut Context,
+    /// The timeout to restore
+    prev: Duration,
+}
+
+impl<'a> Deref for TimeoutGuard<'a> {
+    type Target = Context;
+
+    fn deref(&self) -> &Self::Target {
+        self.ctx
+    }
+}
+
+impl<'a> DerefMut for TimeoutGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ctx
+    }
+}
+
+impl<'a> Drop for TimeoutGuard<'a> {
+    /// Dropping the guard restores the previous timeout.
+    fn drop(&mut self) {
+        let _ = self.ctx.set_timeout(self.prev);
+    }
+}
+
+/// A scan context, used to discover the IIO contexts that are available
+/// through one or more backends.
+///
+/// The scan can be restricted to specific backends, such as "usb" or "ip",
+/// which is useful to skip a slow network (mDNS) discovery when only
+/// looking for locally-attached devices.
+#[derive(Debug)]
+pub struct ScanContext {
+    /// Pointer to the libiio scan context object
+    ctx: *mut ffi::iio_scan_context,
+}
+
+impl ScanContext {
+    /// Creates a scan context for the specified backends.
+    ///
+    /// `backends` The names of the backends to scan, such as "local",
+    ///     "usb", or "ip". If the slice is empty, all the backends
+    ///     available in the library are used.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use industrial_io as iio;
+    ///
+    /// let scan = iio::ScanContext::new(&["usb"]).unwrap();
+    /// for info in scan.contexts().unwrap() {
+    ///     println!("{} [{}]", info.uri, info.description);
+    /// }
+    /// ```
+    pub fn new(backends: &[&str]) -> Result<ScanContext> {
+        let backends = match backends.is_empty() {
+            true => None,
+            false => Some(CString::new(backends.join(","))?),
+        };
+        let pbackends = backends.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+
+        let ctx = unsafe { ffi::iio_create_scan_context(pbackends, 0) };
+        if ctx.is_null() {
+            return Err(Errno::last().into());
+        }
+        Ok(ScanContext { ctx })
+    }
+
+    /// Scans for the contexts that are available through the backends.
+    pub fn contexts(&self) -> Result<Vec<ContextInfo>> {
+        let mut plist: *mut *mut ffi::iio_context_info = ptr::null_mut();
+        let ret = unsafe { ffi::iio_scan_context_get_info_list(self.ctx, &mut plist) };
+        sys_result(ret as i32, ())?;
+
+        let infos = (0..ret as usize)
+            .map(|i| unsafe {
+                let info = *plist.add(i);
+                ContextInfo {
+                    uri: cstring_opt(ffi::iio_context_info_get_uri(info)).unwrap_or_default(),
+                    description: cstring_opt(ffi::iio_context_info_get_description(info))
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        unsafe { ffi::iio_context_info_list_free(plist) };
+        Ok(infos)
+    }
+}
+
+impl Drop for ScanContext {
+    /// Dropping destroys the underlying scan context.
+    fn drop(&mut self) {
+        unsafe { ffi::iio_scan_context_destroy(self.ctx) };
+    }
+}
+
+/// Information about a context discovered by a [`ScanContext`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextInfo {
+    /// The URI that can be used to create the context
+    pub uri: String,
+    /// A description of the context
+    pub description: String,
+}
+
 /// Iterator over the Devices in a Context
 #[derive(Debug)]
 pub struct DeviceIterator<'a> {
@@ -430,8 +1253,17 @@ impl<'a> Iterator for DeviceIterator<'a> {
             Err(_) => None,
         }
     }
+
+    /// The number of remaining devices is known up front, since the library
+    /// can't fail to look one up by an index below the count.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.ctx.num_devices().saturating_sub(self.idx);
+        (n, Some(n))
+    }
 }
 
+impl<'a> ExactSizeIterator for DeviceIterator<'a> {}
+
 /// Iterator over the attributes in a Context
 #[derive(Debug)]
 pub struct AttrIterator<'a> {
@@ -476,6 +1308,130 @@ mod tests {
         //let ctx = ctx.unwrap();
     }
 
+    // See that we can read context attributes by name.
+    #[test]
+    fn attr_read_by_name() {
+        let ctx = Context::new().unwrap();
+        if let Ok((name, val)) = ctx.get_attr(0) {
+            assert_eq!(ctx.attr_read_str(&name).unwrap(), val);
+        }
+        assert!(ctx.attr_read_str("no_such_attribute").is_err());
+        assert!(ctx.attr_read_int("no_such_attribute").is_err());
+    }
+
+    // A local context uses the library, so the versions must match.
+    #[test]
+    fn compatibility_check() {
+        let ctx = Context::new().unwrap();
+        assert!(ctx.compatibility_check().is_empty());
+    }
+
+    // The known description forms should be parsed into fields.
+    #[test]
+    fn parse_description_fields() {
+        let uname = "Linux pluto 5.4.0 #1 SMP PREEMPT Tue Jan 5 armv7l";
+
+        let map = parse_description("local", uname);
+        assert_eq!(map["sysname"], "Linux");
+        assert_eq!(map["hostname"], "pluto");
+        assert_eq!(map["kernel"], "5.4.0");
+        assert_eq!(map["kernel_build"], "#1 SMP PREEMPT Tue Jan 5");
+        assert_eq!(map["machine"], "armv7l");
+
+        let map = parse_description("network", &format!("192.168.2.1 {}", uname));
+        assert_eq!(map["ip"], "192.168.2.1");
+        assert_eq!(map["kernel"], "5.4.0");
+
+        let map = parse_description("usb", "Analog Devices PlutoSDR");
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["raw"], "Analog Devices PlutoSDR");
+    }
+
+    // The summaries should match the devices.
+    #[test]
+    fn device_summaries() {
+        let ctx = Context::new().unwrap();
+        let summaries = ctx.device_summaries();
+        assert_eq!(summaries.len(), ctx.num_devices());
+        for (dev, (id, name)) in ctx.devices().zip(summaries) {
+            assert_eq!(dev.id(), Some(id));
+            assert_eq!(dev.name(), name);
+        }
+    }
+
+    // The dump should list every device in the context.
+    #[test]
+    fn dump() {
+        let ctx = Context::new().unwrap();
+        let s = ctx.dump();
+        assert!(s.starts_with("Context: "));
+        for dev in ctx.devices() {
+            assert!(s.contains(&dev.id().unwrap()));
+        }
+    }
+
+    // The destroy callback should only run when the last clone is dropped.
+    #[test]
+    fn on_destroy() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        let destroyed = Arc::new(AtomicBool::new(false));
+        let ctx = Context::new().unwrap();
+        let flag = Arc::clone(&destroyed);
+        ctx.on_destroy(move || flag.store(true, Ordering::SeqCst));
+
+        let ctx2 = ctx.clone();
+        drop(ctx);
+        assert!(!destroyed.load(Ordering::SeqCst));
+        drop(ctx2);
+        assert!(destroyed.load(Ordering::SeqCst));
+    }
+
+    // Without the environment variable, we should get a local context.
+    #[test]
+    fn from_env_local() {
+        let ctx = Context::from_env("RIIO_NO_SUCH_VARIABLE").unwrap();
+        assert_eq!(ctx.uri().as_deref(), Some("local:"));
+    }
+
+    // A value with a backend scheme should be taken as a full URI.
+    #[test]
+    fn uri_schemes() {
+        assert!(has_uri_scheme("ip:192.168.2.1"));
+        assert!(has_uri_scheme("usb:1.2.3"));
+        assert!(has_uri_scheme("local:"));
+        assert!(!has_uri_scheme("192.168.2.1"));
+        assert!(!has_uri_scheme("fe80::1"));
+    }
+
+    // Transient errors should be retried, up to the number of attempts.
+    #[test]
+    fn retry() {
+        let ctx = Context::new().unwrap();
+        let backoff = Duration::from_millis(1);
+
+        let mut n = 0;
+        let res = ctx.with_retry_backoff(3, backoff, || {
+            n += 1;
+            match n {
+                1 | 2 => Err(Errno::EAGAIN.into()),
+                _ => Ok(n),
+            }
+        });
+        assert_eq!(res.unwrap(), 3);
+
+        let mut n = 0;
+        let res: Result<()> = ctx.with_retry_backoff(3, backoff, || {
+            n += 1;
+            Err(Errno::EIO.into())
+        });
+        assert!(res.is_err());
+        assert_eq!(n, 1);
+    }
+
     // Clone a context and make sure it's reported as same one.
     #[test]
     fn clone_context() {
@@ -522,4 +1478,46 @@ mod tests {
         println!("Context description: {}", desc);
         assert!(!desc.is_empty());
     }
+
+    // Scan the local backend for contexts.
+    #[test]
+    fn scan_local() {
+        let scan = ScanContext::new(&["local"]).unwrap();
+        let infos = scan.contexts().unwrap();
+        assert!(!infos.is_empty());
+    }
+
+    // A failed creation should report the URI.
+    #[test]
+    fn bad_uri_error() {
+        let uri = "xml:/no/such/file.xml";
+        match Context::from_uri(uri) {
+            Err(Error::ContextCreation { uri: err_uri, .. }) => assert_eq!(err_uri, uri),
+            _ => panic!("Expected a context creation error"),
+        }
+    }
+
+    // The guard should restore the previous timeout.
+    #[test]
+    fn timeout_guard() {
+        let mut ctx = Context::new().unwrap();
+        let t1 = Duration::from_millis(1500);
+        let t2 = Duration::from_millis(5000);
+
+        ctx.set_timeout(t1).unwrap();
+        assert_eq!(ctx.set_timeout(t1).unwrap(), Some(t1));
+        {
+            let ctx = ctx.with_timeout(t2).unwrap();
+            assert_eq!(ctx.timeout(), Some(t2));
+        }
+        assert_eq!(ctx.timeout(), Some(t1));
+    }
+
+    // The guard can't be created without a previous timeout to restore.
+    #[test]
+    fn timeout_guard_unset() {
+        let mut ctx = Context::new().unwrap();
+        assert!(ctx.with_timeout(Duration::from_millis(5000)).is_err());
+        assert_eq!(ctx.timeout(), None);
+    }
 }
@@ -11,18 +11,120 @@
 //!
 
 use super::*;
-use crate::{ffi, ATTR_BUF_SIZE};
+use crate::{channel::SYSFS_IIO_DEVICES, ffi, ATTR_BUF_SIZE};
 use nix::errno::Errno;
 use std::{
     any::Any,
+    cell::Cell,
     collections::HashMap,
     ffi::CString,
     fmt::Display,
+    fs::File,
+    io::Read,
+    ops::Deref,
     os::raw::{c_char, c_longlong, c_uint, c_void},
+    path::PathBuf,
     ptr,
     str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+/// The name of the attribute holding the sampling rate of a device.
+pub(crate) const SAMPLING_FREQ_ATTR: &str = "sampling_frequency";
+
+/// The name of the attribute holding the oversampling ratio of a device.
+const OVERSAMPLING_RATIO_ATTR: &str = "oversampling_ratio";
+
+/// The name of the attribute to power down a device or channel.
+const POWERDOWN_ATTR: &str = "powerdown";
+
+/// The name of the attribute holding the clock for timestamps.
+const TIMESTAMP_CLOCK_ATTR: &str = "current_timestamp_clock";
+
+/// The time between reads of an attribute in [`Device::wait_for_attr()`].
+const ATTR_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The delay between attempts in [`Device::attr_read_retry()`].
+const ATTR_BUSY_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The clock used for the timestamps of a device's samples and events.
+///
+/// This corresponds to the `current_timestamp_clock` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampClock {
+    /// The system-wide real-time (wall) clock
+    Realtime,
+    /// The monotonic clock
+    Monotonic,
+    /// The monotonic clock, not subject to NTP adjustments
+    MonotonicRaw,
+    /// A faster, lower precision version of the real-time clock
+    RealtimeCoarse,
+    /// A faster, lower precision version of the monotonic clock
+    MonotonicCoarse,
+    /// The monotonic clock, including time that the system is suspended
+    Boottime,
+    /// International Atomic Time
+    Tai,
+}
+
+impl TimestampClock {
+    /// Gets the name that the kernel uses for the clock.
+    pub fn as_str(&self) -> &'static str {
+        use TimestampClock::*;
+        match *self {
+            Realtime => "realtime",
+            Monotonic => "monotonic",
+            MonotonicRaw => "monotonic_raw",
+            RealtimeCoarse => "realtime_coarse",
+            MonotonicCoarse => "monotonic_coarse",
+            Boottime => "boottime",
+            Tai => "tai",
+        }
+    }
+}
+
+impl Display for TimestampClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for TimestampClock {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        use TimestampClock::*;
+        match s.trim() {
+            "realtime" => Ok(Realtime),
+            "monotonic" => Ok(Monotonic),
+            "monotonic_raw" => Ok(MonotonicRaw),
+            "realtime_coarse" => Ok(RealtimeCoarse),
+            "monotonic_coarse" => Ok(MonotonicCoarse),
+            "boottime" => Ok(Boottime),
+            "tai" => Ok(Tai),
+            _ => Err(Error::StringConversionError),
+        }
+    }
+}
+
+/// The namespaces for the attributes of a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttrNamespace {
+    /// The device-specific attributes
+    Device,
+    /// The buffer-specific attributes
+    Buffer,
+    /// The debug attributes. These typically require root privileges and
+    /// a backend with debugfs support.
+    Debug,
+}
+
 /// An Industrial I/O Device
 ///
 /// This can not be created directly. It is obtained from a context.
@@ -40,6 +142,23 @@ impl Device {
         self.ctx.clone()
     }
 
+    /// Gets the URI of the context to which the device belongs, if known.
+    ///
+    /// See [`Context::uri()`].
+    pub fn uri(&self) -> Option<String> {
+        self.ctx.uri()
+    }
+
+    /// Finds the same device in another context.
+    ///
+    /// This is useful to translate a device into a context that was cloned
+    /// for use in another thread, such as one created from
+    /// [`Context::try_clone_inner()`]. The device is looked up by its ID,
+    /// and `None` is returned if it isn't present in the other context.
+    pub fn in_context(&self, ctx: &Context) -> Option<Device> {
+        ctx.find_device(&self.id()?)
+    }
+
     /// Gets the device ID (e.g. <b><i>iio:device0</i></b>)
     pub fn id(&self) -> Option<String> {
         let pstr = unsafe { ffi::iio_device_get_id(self.dev) };
@@ -69,6 +188,16 @@ impl Device {
         unsafe { ffi::iio_device_is_trigger(self.dev) }
     }
 
+    /// Determines whether the device is a hardware monitor (hwmon), such as
+    /// a temperature or voltage monitor bridged into IIO.
+    ///
+    /// The version of libiio used by this crate has no call to query this,
+    /// so it's determined from the device ID. Newer versions of the library
+    /// give hwmon devices IDs like `hwmon0`.
+    pub fn is_hwmon(&self) -> bool {
+        matches!(self.id(), Some(id) if id.starts_with("hwmon"))
+    }
+
     /// Associate a trigger for this device.
     /// `trigger` The device to be used as a trigger.
     pub fn set_trigger(&self, trigger: &Device) -> Result<()> {
@@ -82,6 +211,79 @@ impl Device {
         sys_result(ret, ())
     }
 
+    /// Runs a function with the device's trigger removed, then restores
+    /// the trigger.
+    ///
+    /// Some one-shot reads must be done with no active trigger. The
+    /// original trigger is restored after the function returns, even if
+    /// it fails or panics. If the device has no trigger, the function is
+    /// simply run. An error from the function takes precedence over one
+    /// from restoring the trigger.
+    pub fn with_trigger_disabled<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        let trig = match self.trigger()? {
+            Some(trig) => trig,
+            None => return f(),
+        };
+
+        self.remove_trigger()?;
+        let mut guard = TriggerRestore {
+            dev: self,
+            trig: Some(trig),
+        };
+        let ret = f()?;
+        guard.restore()?;
+        Ok(ret)
+    }
+
+    /// Gets the trigger currently associated with the device, if any.
+    ///
+    /// This returns `Ok(None)` if no trigger is set, or if the device
+    /// doesn't support triggers at all, and only returns an error for an
+    /// actual failure to query the device.
+    pub fn trigger(&self) -> Result<Option<Device>> {
+        match self.current_trigger() {
+            // The device has no 'trigger/current_trigger' attribute.
+            Err(err) if err.errno() == Some(Errno::ENOENT) => Ok(None),
+            res => res,
+        }
+    }
+
+    /// Gets the trigger currently associated with the device, if any,
+    /// failing with ENOENT if the device doesn't support triggers.
+    fn current_trigger(&self) -> Result<Option<Device>> {
+        let mut trig: *const ffi::iio_device = ptr::null();
+        let ret = unsafe { ffi::iio_device_get_trigger(self.dev, &mut trig) };
+        sys_result(ret, ())?;
+
+        if trig.is_null() {
+            Ok(None)
+        }
+        else {
+            Ok(Some(Device {
+                dev: trig as *mut ffi::iio_device,
+                ctx: self.context(),
+            }))
+        }
+    }
+
+    /// Gets the sampling frequency of the trigger driving this device.
+    ///
+    /// When a device is driven by a software trigger, such as an `hrtimer`
+    /// or `sysfstrig`, the sampling rate is set on the trigger device, not
+    /// the data device. This returns `None` if the device has no trigger,
+    /// or if the trigger doesn't have a sampling frequency.
+    pub fn trigger_frequency(&self) -> Result<Option<f64>> {
+        match self.trigger()? {
+            Some(trig) if trig.has_attr(SAMPLING_FREQ_ATTR) => {
+                trig.attr_read_float(SAMPLING_FREQ_ATTR).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
     // ----- Attributes -----
 
     /// Determines if the device has any attributes
@@ -91,13 +293,67 @@ impl Device {
 
     /// Gets the number of device-specific attributes
     pub fn num_attrs(&self) -> usize {
-        unsafe { ffi::iio_device_get_attrs_count(self.dev) as usize }
+        Attributes::num_attrs(self)
+    }
+
+    /// Gets the number of attributes in the specified namespace
+    pub fn attr_count(&self, ns: AttrNamespace) -> usize {
+        let n = unsafe {
+            match ns {
+                AttrNamespace::Device => ffi::iio_device_get_attrs_count(self.dev),
+                AttrNamespace::Buffer => ffi::iio_device_get_buffer_attrs_count(self.dev),
+                AttrNamespace::Debug => ffi::iio_device_get_debug_attrs_count(self.dev),
+            }
+        };
+        n as usize
+    }
+
+    /// Gets the name of the attribute at the index in the specified
+    /// namespace
+    pub fn attr_name(&self, ns: AttrNamespace, idx: usize) -> Result<String> {
+        let idx = idx as c_uint;
+        let pstr = unsafe {
+            match ns {
+                AttrNamespace::Device => ffi::iio_device_get_attr(self.dev, idx),
+                AttrNamespace::Buffer => ffi::iio_device_get_buffer_attr(self.dev, idx),
+                AttrNamespace::Debug => ffi::iio_device_get_debug_attr(self.dev, idx),
+            }
+        };
+        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+    }
+
+    /// Reads an attribute in the specified namespace
+    ///
+    /// `ns` The namespace of the attribute
+    /// `attr` The name of the attribute
+    pub fn attr_read_ns<T: FromStr + Any>(&self, ns: AttrNamespace, attr: &str) -> Result<T> {
+        let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
+        let (pbuf, n) = (buf.as_mut_ptr(), buf.len());
+        let attr = CString::new(attr)?;
+        let ret = unsafe {
+            match ns {
+                AttrNamespace::Device => {
+                    ffi::iio_device_attr_read(self.dev, attr.as_ptr(), pbuf, n)
+                }
+                AttrNamespace::Buffer => {
+                    ffi::iio_device_buffer_attr_read(self.dev, attr.as_ptr(), pbuf, n)
+                }
+                AttrNamespace::Debug => {
+                    ffi::iio_device_debug_attr_read(self.dev, attr.as_ptr(), pbuf, n)
+                }
+            }
+        };
+        match ns {
+            AttrNamespace::Debug => debug_result(ret as i32, ())?,
+            _ => sys_result(ret as i32, ())?,
+        }
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()).to_str()? };
+        string_to_attr(s.into())
     }
 
     /// Gets the name of the device-specific attribute at the index
     pub fn get_attr(&self, idx: usize) -> Result<String> {
-        let pstr = unsafe { ffi::iio_device_get_attr(self.dev, idx as c_uint) };
-        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+        Attributes::attr_name(self, idx)
     }
 
     /// Try to find a device-specific attribute by its name
@@ -107,7 +363,10 @@ impl Device {
         cstring_opt(pstr)
     }
 
-    /// Determines if a buffer-specific attribute exists
+    /// Determines if a device-specific attribute exists
+    ///
+    /// This uses the library's direct lookup by name, rather than
+    /// iterating through all of the device's attributes.
     pub fn has_attr(&self, name: &str) -> bool {
         let cname = cstring_or_bail_false!(name);
         let pstr = unsafe { ffi::iio_device_find_attr(self.dev, cname.as_ptr()) };
@@ -122,40 +381,125 @@ impl Device {
         string_to_attr(sval)
     }
 
+    /// Reads a device-specific attribute, retrying if the device is busy.
+    ///
+    /// Some attributes transiently fail with `EBUSY` while the device is in
+    /// the middle of an operation, such as a reconfiguration. This makes up
+    /// to `attempts` reads, with a short delay between them, as long as the
+    /// read fails with `EBUSY`. Any other error, or the last `EBUSY` once
+    /// the attempts are exhausted, is returned.
+    pub fn attr_read_retry<T: FromStr + Any>(&self, attr: &str, attempts: u32) -> Result<T> {
+        let mut n = 1;
+        loop {
+            match self.attr_read(attr) {
+                Err(err) if err.errno() == Some(Errno::EBUSY) && n < attempts => {
+                    thread::sleep(ATTR_BUSY_RETRY_DELAY);
+                    n += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
     /// Reads a device-specific attribute as a string
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_str(&self, attr: &str) -> Result<String> {
+        Attributes::attr_read(self, attr)
+    }
+
+    /// The same as [`Device::attr_read_str()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_str_cstr(&self, attr: &CStr) -> Result<String> {
         let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
-        let attr = CString::new(attr)?;
         let ret = unsafe {
             ffi::iio_device_attr_read(self.dev, attr.as_ptr(), buf.as_mut_ptr(), buf.len())
         };
         sys_result(ret as i32, ())?;
-        let s = unsafe {
-            CStr::from_ptr(buf.as_ptr())
-                .to_str()
-                .map_err(|_| Error::StringConversionError)?
-        };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()).to_str()? };
         Ok(s.into())
     }
 
+    /// Reads a device-specific attribute as raw bytes.
+    ///
+    /// This is for binary attributes, like calibration tables. The
+    /// library's attribute API is for text, and replaces the last byte of
+    /// the value with a NUL terminator, so this reads the attribute's sysfs
+    /// file directly instead. That means it's only available for devices
+    /// in a local context.
+    ///
+    /// The buffer is cleared, then filled with the whole value of the
+    /// attribute. On success, this returns the number of bytes read, which
+    /// is also the length of the buffer.
+    pub fn attr_read_raw_bytes(&self, attr: &str, buf: &mut Vec<u8>) -> Result<usize> {
+        if self.ctx.name() != "local" {
+            return Err(Error::General(
+                "Binary attributes can only be read from a local context".into(),
+            ));
+        }
+        let id = self
+            .id()
+            .ok_or_else(|| Error::General("The device has no ID".into()))?;
+        let path = PathBuf::from(SYSFS_IIO_DEVICES).join(id).join(attr);
+
+        buf.clear();
+        let n = File::open(path)?.read_to_end(buf)?;
+        Ok(n)
+    }
+
+    /// Reads a device-specific attribute with a fixed set of string values
+    /// into an enumerated type.
+    ///
+    /// This is useful for attributes like operating modes, which can be
+    /// parsed into a user type that implements `FromStr`. If the value can't
+    /// be parsed, the error contains the offending string.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_enum<T: FromStr>(&self, attr: &str) -> Result<T> {
+        let sval = self.attr_read_str(attr)?;
+        let sval = sval.trim();
+        T::from_str(sval).map_err(|_| {
+            Error::General(format!(
+                "Unrecognized value '{}' for attribute '{}'",
+                sval, attr
+            ))
+        })
+    }
+
     /// Reads a device-specific attribute as a boolean
     ///
+    /// This accepts the various encodings that devices use, such as
+    /// "1"/"0", "true"/"false", and "Y"/"N".
+    ///
     /// `attr` The name of the attribute
     pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
-        let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_read_bool(self.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        self.attr_read_bool_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Device::attr_read_bool()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_bool_cstr(&self, attr: &CStr) -> Result<bool> {
+        let sval = self.attr_read_str_cstr(attr)?;
+        parse_bool(&sval)
     }
 
     /// Reads a device-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_int(&self, attr: &str) -> Result<i64> {
+        self.attr_read_int_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Device::attr_read_int()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_int_cstr(&self, attr: &CStr) -> Result<i64> {
         let mut val: c_longlong = 0;
-        let attr = CString::new(attr)?;
         let ret = unsafe { ffi::iio_device_attr_read_longlong(self.dev, attr.as_ptr(), &mut val) };
         sys_result(ret, val as i64)
     }
@@ -164,12 +508,92 @@ impl Device {
     ///
     /// `attr` The name of the attribute
     pub fn attr_read_float(&self, attr: &str) -> Result<f64> {
+        self.attr_read_float_cstr(&CString::new(attr)?)
+    }
+
+    /// The same as [`Device::attr_read_float()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_float_cstr(&self, attr: &CStr) -> Result<f64> {
         let mut val: f64 = 0.0;
-        let attr = CString::new(attr)?;
         let ret = unsafe { ffi::iio_device_attr_read_double(self.dev, attr.as_ptr(), &mut val) };
         sys_result(ret, val)
     }
 
+    /// Waits for a device-specific attribute to reach a value.
+    ///
+    /// This repeatedly reads the attribute, as a string, until `predicate`
+    /// returns true for the value, or until the timeout expires, in which
+    /// case it returns [`Error::Timeout`]. The attribute is read every 10ms.
+    ///
+    /// `attr` The name of the attribute
+    /// `predicate` A test of the value of the attribute
+    /// `timeout` The maximum time to wait
+    pub fn wait_for_attr<F>(&self, attr: &str, predicate: F, timeout: Duration) -> Result<()>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if predicate(self.attr_read_str(attr)?.trim()) {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout);
+            }
+            thread::sleep(ATTR_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Watches a device-specific attribute for changes, from a background
+    /// thread.
+    ///
+    /// A context can't be shared between threads, so this creates a clone
+    /// of the device's context for a thread that reads the attribute, as a
+    /// string, at the given interval. The current value is sent to the
+    /// returned watcher right away, then each new value is sent whenever
+    /// the attribute changes.
+    ///
+    /// The thread stops within one interval of the watcher being dropped.
+    /// It also stops if the attribute can't be read, which closes the
+    /// channel.
+    pub fn watch_attr(&self, attr: &str, interval: Duration) -> Result<AttrWatcher> {
+        let id = self
+            .id()
+            .ok_or_else(|| Error::General("The device has no ID".into()))?;
+        let inner = self.ctx.try_clone_inner()?;
+        let attr = attr.to_string();
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thr_stop = Arc::clone(&stop);
+
+        thread::spawn(move || {
+            let ctx = Context::from_inner(inner);
+            let dev = match ctx.find_device(&id) {
+                Some(dev) => dev,
+                None => return,
+            };
+
+            let mut last = None;
+            while !thr_stop.load(Ordering::Relaxed) {
+                let val = match dev.attr_read_str(&attr) {
+                    Ok(val) => val,
+                    Err(_) => break,
+                };
+                if last.as_ref() != Some(&val) {
+                    if tx.send(val.clone()).is_err() {
+                        break;
+                    }
+                    last = Some(val);
+                }
+                thread::sleep(interval);
+            }
+        });
+        Ok(AttrWatcher { rx, stop })
+    }
+
     /// Reads all the device-specific attributes.
     /// This is especially useful when using the network backend to
     /// retrieve all the attributes with a single call.
@@ -180,24 +604,52 @@ impl Device {
         sys_result(ret, map)
     }
 
+    /// Reads all the device-specific attributes, detecting the type of
+    /// each value.
+    ///
+    /// See [`AttrValue`] for the rules used to detect the types.
+    pub fn attr_read_all_typed(&self) -> Result<HashMap<String, AttrValue>> {
+        let map = self.attr_read_all()?;
+        Ok(map
+            .into_iter()
+            .map(|(attr, val)| {
+                let val = AttrValue::from(val.as_str());
+                (attr, val)
+            })
+            .collect())
+    }
+
     /// Writes a device-specific attribute
     ///
+    /// On success, this returns the number of bytes written.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: Display + Any>(&self, attr: &str, val: T) -> Result<usize> {
         let sval = attr_to_string(val)?;
         self.attr_write_str(attr, &sval)
     }
 
     /// Writes a device-specific attribute as a string
     ///
+    /// On success, this returns the number of bytes written, which callers
+    /// can check to verify that the whole value was accepted.
+    ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
-        let attr = CString::new(attr)?;
+    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<usize> {
+        Attributes::attr_write(self, attr, val)
+    }
+
+    /// The same as [`Device::attr_write_str()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_str_cstr(&self, attr: &CStr, val: &str) -> Result<usize> {
         let val = CString::new(val)?;
         let ret = unsafe { ffi::iio_device_attr_write(self.dev, attr.as_ptr(), val.as_ptr()) };
-        sys_result(ret as i32, ())
+        sys_result(ret as i32, ret as usize)
     }
 
     /// Writes a device-specific attribute as a boolean
@@ -205,7 +657,15 @@ impl Device {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_bool(&self, attr: &str, val: bool) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_bool_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Device::attr_write_bool()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_bool_cstr(&self, attr: &CStr, val: bool) -> Result<()> {
         let ret = unsafe { ffi::iio_device_attr_write_bool(self.dev, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
@@ -215,7 +675,15 @@ impl Device {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_int(&self, attr: &str, val: i64) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_int_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Device::attr_write_int()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_int_cstr(&self, attr: &CStr, val: i64) -> Result<()> {
         let ret = unsafe { ffi::iio_device_attr_write_longlong(self.dev, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
@@ -225,7 +693,15 @@ impl Device {
     /// `attr` The name of the attribute
     /// `val` The value to write
     pub fn attr_write_float(&self, attr: &str, val: f64) -> Result<()> {
-        let attr = CString::new(attr)?;
+        self.attr_write_float_cstr(&CString::new(attr)?, val)
+    }
+
+    /// The same as [`Device::attr_write_float()`], but with the name of the
+    /// attribute as a C string, which avoids an allocation on each call.
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_float_cstr(&self, attr: &CStr, val: f64) -> Result<()> {
         let ret = unsafe { ffi::iio_device_attr_write_double(self.dev, attr.as_ptr(), val) };
         sys_result(ret, ())
     }
@@ -235,6 +711,172 @@ impl Device {
         AttrIterator { dev: self, idx: 0 }
     }
 
+    // Determines if a buffer-specific attribute exists for the device.
+    fn has_buffer_attr(&self, name: &str) -> bool {
+        let cname = cstring_or_bail_false!(name);
+        let pstr = unsafe { ffi::iio_device_find_buffer_attr(self.dev, cname.as_ptr()) };
+        !pstr.is_null()
+    }
+
+    // Reads a buffer-specific attribute of the device as an integer.
+    fn buffer_attr_read_int(&self, attr: &str) -> Result<i64> {
+        let mut val: c_longlong = 0;
+        let attr = CString::new(attr)?;
+        let ret =
+            unsafe { ffi::iio_device_buffer_attr_read_longlong(self.dev, attr.as_ptr(), &mut val) };
+        sys_result(ret, val as i64)
+    }
+
+    /// Gets the sampling frequency of the device, in Hz.
+    ///
+    /// This reads the `sampling_frequency` attribute of the device, or if
+    /// it doesn't have one, that of its trigger. It is an error if neither
+    /// has the attribute.
+    pub fn sampling_frequency(&self) -> Result<f64> {
+        if self.has_attr(SAMPLING_FREQ_ATTR) {
+            return self.attr_read_float(SAMPLING_FREQ_ATTR);
+        }
+        self.trigger_frequency()?.ok_or_else(|| {
+            Error::General("Neither the device nor its trigger has a sampling frequency".into())
+        })
+    }
+
+    /// Gets the sampling frequencies that the device supports.
+    pub fn available_sampling_frequencies(&self) -> Result<AvailableValues> {
+        self.attr_read_str("sampling_frequency_available")?.parse()
+    }
+
+    /// Sets the sampling frequency to the supported value that is nearest
+    /// to the one requested.
+    ///
+    /// Writing an unsupported rate might fail or silently be changed by
+    /// the driver, so this picks the closest of the device's available
+    /// frequencies, sets it, and returns the frequency that was set.
+    pub fn set_nearest_sampling_frequency(&self, hz: f64) -> Result<f64> {
+        let freq = self
+            .available_sampling_frequencies()?
+            .nearest(hz)
+            .ok_or_else(|| Error::General("No sampling frequencies available".into()))?;
+        self.attr_write_float(SAMPLING_FREQ_ATTR, freq)?;
+        Ok(freq)
+    }
+
+    // ----- Hardware FIFO -----
+
+    /// Determines if the hardware FIFO of the device is enabled.
+    ///
+    /// This is `false` if the device doesn't have a hardware FIFO.
+    pub fn is_hwfifo_enabled(&self) -> Result<bool> {
+        if !self.has_buffer_attr("hwfifo_enabled") {
+            return Ok(false);
+        }
+        Ok(self.buffer_attr_read_int("hwfifo_enabled")? != 0)
+    }
+
+    /// Gets the watermark of the hardware FIFO, if the device has one.
+    ///
+    /// Some sensors, like low-power IMUs, batch samples in a hardware FIFO
+    /// and only wake the host when the number of samples reaches the
+    /// watermark. This returns `None` if the device doesn't have a
+    /// hardware FIFO.
+    pub fn hwfifo_watermark(&self) -> Result<Option<u32>> {
+        if !self.has_buffer_attr("hwfifo_watermark") {
+            return Ok(None);
+        }
+        let val = self.buffer_attr_read_int("hwfifo_watermark")?;
+        Ok(Some(val as u32))
+    }
+
+    /// Sets the watermark of the hardware FIFO.
+    ///
+    /// The kernel doesn't allow the `hwfifo_watermark` attribute to be
+    /// written directly. Instead this sets the buffer `watermark`, which
+    /// the driver uses to program the hardware FIFO when the buffer is
+    /// enabled.
+    pub fn set_hwfifo_watermark(&self, watermark: u32) -> Result<()> {
+        let attr = CString::new("watermark")?;
+        let ret = unsafe {
+            ffi::iio_device_buffer_attr_write_longlong(
+                self.dev,
+                attr.as_ptr(),
+                c_longlong::from(watermark),
+            )
+        };
+        sys_result(ret, ())
+    }
+
+    // ----- Power Management -----
+
+    /// Powers down or up the device.
+    ///
+    /// This writes the `powerdown` attribute of the device, if it has one,
+    /// and that of each of its channels that has one, such as the outputs
+    /// of a DAC. It is an error if neither the device nor any channel has
+    /// the attribute.
+    pub fn set_powerdown(&self, on: bool) -> Result<()> {
+        let mut found = false;
+        if self.has_attr(POWERDOWN_ATTR) {
+            self.attr_write_bool(POWERDOWN_ATTR, on)?;
+            found = true;
+        }
+        for chan in self.channels().filter(|ch| ch.has_attr(POWERDOWN_ATTR)) {
+            chan.attr_write_bool(POWERDOWN_ATTR, on)?;
+            found = true;
+        }
+        if !found {
+            return Err(Error::General(
+                "Neither the device nor its channels can be powered down".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Gets the clock used for the device's timestamps.
+    pub fn timestamp_clock(&self) -> Result<TimestampClock> {
+        self.attr_read_enum(TIMESTAMP_CLOCK_ATTR)
+    }
+
+    /// Sets the clock used for the device's timestamps.
+    pub fn set_timestamp_clock(&self, clock: TimestampClock) -> Result<()> {
+        self.attr_write_str(TIMESTAMP_CLOCK_ATTR, clock.as_str())?;
+        Ok(())
+    }
+
+    // ----- Oversampling -----
+
+    /// Gets the oversampling ratio of the device.
+    ///
+    /// An ADC that oversamples takes this number of conversions for each
+    /// sample that it reports, averaging them to reduce noise. This returns
+    /// `None` if the device doesn't support oversampling.
+    pub fn oversampling_ratio(&self) -> Result<Option<u32>> {
+        if !self.has_attr(OVERSAMPLING_RATIO_ATTR) {
+            return Ok(None);
+        }
+        let val = self.attr_read_int(OVERSAMPLING_RATIO_ATTR)?;
+        Ok(Some(val as u32))
+    }
+
+    /// Sets the oversampling ratio of the device.
+    ///
+    /// If the device has an `oversampling_ratio_available` attribute, the
+    /// ratio is checked against it first, and an error is returned if it
+    /// isn't supported.
+    pub fn set_oversampling_ratio(&self, ratio: u32) -> Result<()> {
+        let avail_attr = format!("{}_available", OVERSAMPLING_RATIO_ATTR);
+        if self.has_attr(&avail_attr) {
+            let avail: AvailableValues = self.attr_read_str(&avail_attr)?.parse()?;
+            let val = f64::from(ratio);
+            if avail.nearest(val) != Some(val) {
+                return Err(Error::General(format!(
+                    "Unsupported oversampling ratio: {}",
+                    ratio
+                )));
+            }
+        }
+        self.attr_write_int(OVERSAMPLING_RATIO_ATTR, i64::from(ratio))
+    }
+
     // ----- Channels -----
 
     /// Gets the number of channels on the device
@@ -242,6 +884,19 @@ impl Device {
         unsafe { ffi::iio_device_get_channels_count(self.dev) as usize }
     }
 
+    /// Gets the number of input channels on the device
+    pub fn num_input_channels(&self) -> usize {
+        self.channels().filter(|ch| !ch.is_output()).count()
+    }
+
+    /// Gets the number of output channels on the device
+    ///
+    /// This can be used to quickly determine if the device is an output
+    /// device, such as a DAC.
+    pub fn num_output_channels(&self) -> usize {
+        self.channels().filter(|ch| ch.is_output()).count()
+    }
+
     /// Gets a channel by index
     pub fn get_channel(&self, idx: usize) -> Result<Channel> {
         let chan = unsafe { ffi::iio_device_get_channel(self.dev, idx as c_uint) };
@@ -270,29 +925,352 @@ impl Device {
         }
     }
 
+    /// Finds the first channel with the given type and direction, and
+    /// modifier, if one is given.
+    ///
+    /// This locates a channel, like the Z axis of an accelerometer, by what
+    /// it measures, which is more robust across drivers than by its ID.
+    pub fn find_channel_by(
+        &self,
+        ty: ChannelType,
+        modifier: Option<Modifier>,
+        is_output: bool,
+    ) -> Option<Channel> {
+        self.channels().find(|ch| {
+            ch.is_output() == is_output
+                && ch.channel_type() == ty
+                && match modifier {
+                    Some(m) => ch.modifier() == m,
+                    None => true,
+                }
+        })
+    }
+
     /// Gets an iterator for the channels in the device
     pub fn channels(&self) -> ChannelIterator {
         ChannelIterator { dev: self, idx: 0 }
     }
 
+    /// Gets an iterator for the scan element channels in the device.
+    ///
+    /// These are the channels that can be captured in a buffer.
+    pub fn scan_channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.channels().filter(|ch| ch.is_scan_element())
+    }
+
+    /// Enables all the scan element channels of the device.
+    pub fn enable_all_channels(&self) {
+        for ch in self.scan_channels() {
+            ch.enable();
+        }
+    }
+
+    /// Disables all the scan element channels of the device.
+    pub fn disable_all_channels(&self) {
+        for ch in self.scan_channels() {
+            ch.disable();
+        }
+    }
+
+    /// Enables channels, by ID, for the lifetime of the returned guard.
+    ///
+    /// When the guard is dropped, each of the channels is returned to the
+    /// enabled state it had before the call. This keeps a temporary capture
+    /// from leaving the device in a different state, even on an early
+    /// return. Input channels are searched first, then output channels.
+    /// If any ID isn't found, no channels are enabled.
+    pub fn enable_channels_scoped(&self, ids: &[&str]) -> Result<ChannelGuard> {
+        let chans = ids
+            .iter()
+            .map(|id| {
+                self.find_channel(id, false)
+                    .or_else(|| self.find_channel(id, true))
+                    .ok_or_else(|| Error::General(format!("No channel '{}'", id)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let prev = chans
+            .into_iter()
+            .map(|ch| {
+                let enabled = ch.is_enabled();
+                ch.enable();
+                (ch, enabled)
+            })
+            .collect();
+        Ok(ChannelGuard { prev })
+    }
+
+    /// Gets the enabled state of each of the channels of the device, in
+    /// channel index order.
+    ///
+    /// This can be kept as a snapshot, and compared against later with
+    /// [`Device::channels_changed_since()`].
+    pub fn channel_enable_states(&self) -> Vec<bool> {
+        self.channels().map(|ch| ch.is_enabled()).collect()
+    }
+
+    /// Gets the indexes of the channels whose enabled state differs from a
+    /// snapshot taken with [`Device::channel_enable_states()`].
+    ///
+    /// When several parts of an application share a device, this detects
+    /// that another part enabled or disabled channels, which would
+    /// invalidate an existing buffer. Any channel not covered by the
+    /// snapshot is reported as changed.
+    ///
+    /// The library keeps the enabled state of the channels in memory, for
+    /// each of its contexts, so this only detects the changes made through
+    /// clones of the same [`Context`]. Changes made through a separate
+    /// context, including one from [`Context::try_clone_inner()`], or by
+    /// another process, aren't seen.
+    pub fn channels_changed_since(&self, snapshot: &[bool]) -> Vec<usize> {
+        self.channel_enable_states()
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, enabled)| snapshot.get(i) != Some(&enabled))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Reads the `raw` attribute of each of the channels, in order.
+    ///
+    /// This is a simple way to get a snapshot of a few channels from a slow
+    /// sensor without having to set up a buffer and trigger. It fails if
+    /// any of the channels doesn't have a `raw` attribute.
+    pub fn read_raw_channels(&self, chans: &[&Channel]) -> Result<Vec<i64>> {
+        chans
+            .iter()
+            .map(|chan| {
+                if !chan.has_attr("raw") {
+                    return Err(Error::General(format!(
+                        "Channel '{}' has no 'raw' attribute",
+                        chan.id().unwrap_or_default()
+                    )));
+                }
+                chan.attr_read_int("raw")
+            })
+            .collect()
+    }
+
+    /// Reads the `raw` attribute of all the input channels of the device.
+    ///
+    /// This grabs the instantaneous value of each channel, which is useful
+    /// for slow-changing sensors and status displays, without setting up a
+    /// buffer. The values are keyed by channel ID. Channels that don't have
+    /// a `raw` attribute, like a timestamp, are skipped rather than causing
+    /// the whole call to fail.
+    pub fn snapshot_raw(&self) -> Result<HashMap<String, i64>> {
+        let mut map = HashMap::new();
+        for chan in self.channels() {
+            if chan.is_output() || !chan.has_attr("raw") {
+                continue;
+            }
+            if let Some(id) = chan.id() {
+                map.insert(id, chan.attr_read_int("raw")?);
+            }
+        }
+        Ok(map)
+    }
+
+    /// Reads the X, Y, and Z axes of a triaxial sensor, such as an
+    /// accelerometer, gyroscope, or magnetometer.
+    ///
+    /// This finds the input channels of the given type with the X, Y, and
+    /// Z modifiers, and reads the `raw` value of each, applying the
+    /// channel's offset and scale, if it has them. It fails if any of the
+    /// axes is missing.
+    pub fn read_vector3(&self, base: ChannelType) -> Result<[f64; 3]> {
+        let axes = [Modifier::X, Modifier::Y, Modifier::Z];
+
+        let mut v = [0.0; 3];
+        for (val, axis) in v.iter_mut().zip(axes.iter()) {
+            let chan = self
+                .find_channel_by(base, Some(*axis), false)
+                .ok_or_else(|| {
+                    Error::General(format!("No {:?} axis channel of type {:?}", axis, base))
+                })?;
+
+            *val = chan.to_physical(chan.attr_read_int("raw")?)?;
+        }
+        Ok(v)
+    }
+
+    /// Gets the binary layout of the device's samples.
+    ///
+    /// This collects the index, data format, and offset within a sample of
+    /// each of the scan element channels, assuming that all of them are
+    /// enabled. This is useful for tools that generate code or
+    /// documentation for a device.
+    pub fn layout(&self) -> DeviceLayout {
+        let mut chans: Vec<(usize, Channel)> = self
+            .scan_channels()
+            .filter_map(|ch| ch.index().ok().map(|idx| (idx, ch)))
+            .collect();
+        chans.sort_by_key(|(idx, _)| *idx);
+
+        let sizes: Vec<usize> = chans
+            .iter()
+            .map(|(_, ch)| ch.data_format().byte_length())
+            .collect();
+        let (offsets, sample_size) = scan_offsets(&sizes);
+
+        let channels = chans
+            .into_iter()
+            .zip(offsets)
+            .map(|((index, ch), offset)| {
+                let fmt = ch.data_format();
+                ChannelLayout {
+                    id: ch.id().unwrap_or_default(),
+                    index,
+                    offset,
+                    bits: fmt.bits(),
+                    length: fmt.length(),
+                    shift: fmt.shift(),
+                    is_signed: fmt.is_signed(),
+                    is_big_endian: fmt.is_big_endian(),
+                }
+            })
+            .collect();
+
+        DeviceLayout {
+            channels,
+            sample_size,
+        }
+    }
+
+    /// Checks that the device is ready for buffered capture.
+    ///
+    /// This verifies that:
+    ///
+    /// - the device has at least one scan element channel,
+    /// - the sample size is non-zero with a channel enabled, and
+    /// - if the device supports triggers, that one is set.
+    ///
+    /// It returns an error describing the first prerequisite that is
+    /// missing. If no channels were enabled, one is enabled temporarily to
+    /// check the sample size, and the enabled state is restored afterward.
+    pub fn probe_capture(&self) -> Result<()> {
+        let scan_chans: Vec<Channel> = self.scan_channels().collect();
+
+        if scan_chans.is_empty() {
+            return Err(Error::General(
+                "The device has no scan element channels, so it can't be used for buffered \
+                 capture"
+                    .into(),
+            ));
+        }
+
+        let trial_chan = match scan_chans.iter().any(|ch| ch.is_enabled()) {
+            true => None,
+            false => Some(&scan_chans[0]),
+        };
+        if let Some(ch) = trial_chan {
+            ch.enable();
+        }
+        let sample_size = self.sample_size();
+        if let Some(ch) = trial_chan {
+            ch.disable();
+        }
+
+        if sample_size? == 0 {
+            return Err(Error::General(
+                "The device reports a sample size of zero with channels enabled".into(),
+            ));
+        }
+
+        match self.current_trigger() {
+            Ok(None) => Err(Error::General(
+                "The device supports triggers, but none is set. Set one with \
+                 Device::set_trigger()"
+                    .into(),
+            )),
+            Err(err) if err.errno() != Some(Errno::ENOENT) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
     // ----- Buffer Functions -----
 
+    /// Opens a stream of the events reported by the device.
+    ///
+    /// This reads events directly from the kernel, so it is only available
+    /// for devices in a local context. The kernel only allows the device
+    /// to be opened once at a time, so this fails with `EBUSY` while a
+    /// buffer is open for the device.
+    pub fn event_stream(&self) -> Result<EventStream> {
+        EventStream::new(self)
+    }
+
+    /// Starts building a capture pipeline for the device.
+    ///
+    /// See [`CaptureBuilder`].
+    pub fn capture(&self) -> CaptureBuilder {
+        CaptureBuilder::new(self)
+    }
+
     /// Creates a buffer for the device.
     ///
+    /// The version of libiio used by this crate supports only a single
+    /// buffer per device at a time. Multiple concurrent buffers require
+    /// a newer library and kernel. If the device is busy, which usually
+    /// means that it already has a buffer or an open [`EventStream`], this
+    /// returns [`Error::DeviceBusy`], and the buffer or stream must be
+    /// dropped before a new buffer can be created.
+    ///
     /// `sample_count` The number of samples the buffer should hold
     /// `cyclic` Whether to enable cyclic mode.
     pub fn create_buffer(&self, sample_count: usize, cyclic: bool) -> Result<Buffer> {
         let buf = unsafe { ffi::iio_device_create_buffer(self.dev, sample_count, cyclic) };
         if buf.is_null() {
-            return Err(Errno::last().into());
+            let err = Errno::last();
+            if err == Errno::EBUSY {
+                return Err(Error::DeviceBusy(err));
+            }
+            return Err(err.into());
         }
         Ok(Buffer {
             buf,
             cap: sample_count,
             dev: self.clone(),
+            chans: self.channels().filter(|ch| ch.is_enabled()).collect(),
+            filled: Cell::new(buffer_data_len(buf)),
+            cyclic,
         })
     }
 
+    /// Creates a buffer that captures exactly the given channels.
+    ///
+    /// This enables each of the channels, disables all the others, and then
+    /// creates the buffer. Capturing only the channels that are needed can
+    /// greatly reduce the bandwidth of a network context. If the buffer
+    /// can't be created, the channels are returned to the enabled states
+    /// they had before the call.
+    pub fn create_buffer_for_channels(
+        &self,
+        chans: &[&Channel],
+        sample_count: usize,
+        cyclic: bool,
+    ) -> Result<Buffer> {
+        let prev = self.enable_exactly(chans);
+        let res = self.create_buffer(sample_count, cyclic);
+        if res.is_err() {
+            self.restore_enable_states(&prev);
+        }
+        res
+    }
+
+    /// Stops the output from a cyclic buffer that was created for this
+    /// device.
+    ///
+    /// Once pushed, a cyclic buffer keeps repeating its samples, and on
+    /// many drivers the only way to stop the loop is to destroy the buffer.
+    /// This cancels any pending operations on the buffer and then destroys
+    /// it, which disables the buffer in the kernel.
+    pub fn stop_cyclic_output(&self, buf: Buffer) -> Result<()> {
+        buf.cancel();
+        drop(buf);
+        Ok(())
+    }
+
     // ----- Low-level & Debug functions -----
 
     /// Gets the current sample size, in bytes.
@@ -303,17 +1281,154 @@ impl Device {
         sys_result(ret as i32, ret as usize)
     }
 
+    /// Gets the sample size, in bytes, as if only the given channels were
+    /// enabled.
+    ///
+    /// This is useful to plan the memory for a capture of a subset of the
+    /// channels. The channels are temporarily enabled to measure the size,
+    /// then all the channels are returned to their previous enabled states,
+    /// even if the measurement fails.
+    pub fn sample_size_for(&self, chans: &[&Channel]) -> Result<usize> {
+        let prev = self.enable_exactly(chans);
+        let res = self.sample_size();
+        self.restore_enable_states(&prev);
+        res
+    }
+
+    // Enables the given channels and disables all the others, returning
+    // the previous enabled states, from `channel_enable_states()`.
+    fn enable_exactly(&self, chans: &[&Channel]) -> Vec<bool> {
+        let prev = self.channel_enable_states();
+        for ch in self.channels() {
+            ch.disable();
+        }
+        for ch in chans {
+            ch.enable();
+        }
+        prev
+    }
+
+    // Restores the enabled states of the channels from a snapshot.
+    fn restore_enable_states(&self, states: &[bool]) {
+        for (ch, &enabled) in self.channels().zip(states) {
+            if enabled {
+                ch.enable();
+            }
+            else {
+                ch.disable();
+            }
+        }
+    }
+
+    /// Gets the data rate of a capture with the enabled channels, in bytes
+    /// per second.
+    ///
+    /// This is the [sample size][Device::sample_size()] times the
+    /// [sampling frequency][Device::sampling_frequency()], and is an error
+    /// if either of them is unavailable.
+    ///
+    /// The sampling frequency is the rate at which samples are reported,
+    /// so for a device that is [oversampling][Device::oversampling_ratio()]
+    /// it already accounts for the ratio, and the conversion rate of the
+    /// ADC is higher than the data rate by that factor.
+    pub fn throughput_bytes_per_sec(&self) -> Result<f64> {
+        let sz = self.sample_size()?;
+        let freq = self.sampling_frequency()?;
+        Ok(sz as f64 * freq)
+    }
+
     /// Gets the value of a hardware register
+    ///
+    /// This requires debug access to the device. If that isn't available,
+    /// this returns [`Error::DebugNotAvailable`].
     pub fn reg_read(&self, addr: u32) -> Result<u32> {
         let mut val: u32 = 0;
         let ret = unsafe { ffi::iio_device_reg_read(self.dev, addr, &mut val) };
-        sys_result(ret as i32, val)
+        debug_result(ret as i32, val)
     }
 
     /// Sets the value of a hardware register
+    ///
+    /// This requires debug access to the device. If that isn't available,
+    /// this returns [`Error::DebugNotAvailable`].
     pub fn reg_write(&self, addr: u32, val: u32) -> Result<()> {
         let ret = unsafe { ffi::iio_device_reg_write(self.dev, addr, val) };
-        sys_result(ret as i32, ())
+        debug_result(ret as i32, ())
+    }
+}
+
+/// The layout of a scan element channel in the samples of a device.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelLayout {
+    /// The channel ID
+    pub id: String,
+    /// The scan index of the channel
+    pub index: usize,
+    /// The offset of the channel's data within a sample, in bytes
+    pub offset: usize,
+    /// The number of valid bits of data
+    pub bits: u32,
+    /// The storage size of the data, in bits
+    pub length: u32,
+    /// The right-shift to apply to the data
+    pub shift: u32,
+    /// Whether the data is signed
+    pub is_signed: bool,
+    /// Whether the data is big-endian
+    pub is_big_endian: bool,
+}
+
+/// The binary layout of the samples of a device, as captured in a buffer.
+///
+/// This describes the layout when all the scan element channels are
+/// enabled.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceLayout {
+    /// The scan element channels, in order of their scan index
+    pub channels: Vec<ChannelLayout>,
+    /// The total size of a sample, in bytes
+    pub sample_size: usize,
+}
+
+// Computes the byte offsets of the elements in a sample, given the size of
+// each, in scan order. Each element is aligned to its own size, and the
+// total is aligned to the largest element, as done by the kernel.
+// Returns the offsets and the total sample size.
+fn scan_offsets(sizes: &[usize]) -> (Vec<usize>, usize) {
+    let align = |n: usize, sz: usize| match n.checked_rem(sz) {
+        Some(r) if r != 0 => n + sz - r,
+        _ => n,
+    };
+
+    let mut offsets = Vec::with_capacity(sizes.len());
+    let mut nbytes = 0;
+    for &sz in sizes {
+        nbytes = align(nbytes, sz);
+        offsets.push(nbytes);
+        nbytes += sz;
+    }
+    let largest = sizes.iter().copied().max().unwrap_or(0);
+    (offsets, align(nbytes, largest))
+}
+
+impl Attributes for Device {
+    fn num_attrs(&self) -> usize {
+        unsafe { ffi::iio_device_get_attrs_count(self.dev) as usize }
+    }
+
+    fn attr_name(&self, idx: usize) -> Result<String> {
+        let pstr = unsafe { ffi::iio_device_get_attr(self.dev, idx as c_uint) };
+        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+    }
+
+    fn attr_read(&self, name: &str) -> Result<String> {
+        self.attr_read_str_cstr(&CString::new(name)?)
+    }
+
+    fn attr_write(&self, name: &str, val: &str) -> Result<usize> {
+        self.attr_write_str_cstr(&CString::new(name)?, val)
     }
 }
 
@@ -325,6 +1440,88 @@ impl PartialEq for Device {
     }
 }
 
+/// A watcher for changes to a device attribute, from a background thread.
+///
+/// This is created by [`Device::watch_attr()`], and dereferences to the
+/// receiver for the new values of the attribute. Dropping it stops the
+/// thread.
+#[derive(Debug)]
+pub struct AttrWatcher {
+    /// The receiver for the new values
+    rx: mpsc::Receiver<String>,
+    /// The flag to tell the thread to stop
+    stop: Arc<AtomicBool>,
+}
+
+impl Deref for AttrWatcher {
+    type Target = mpsc::Receiver<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl Drop for AttrWatcher {
+    /// Dropping the watcher tells the thread to stop.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A guard that returns channels to their previous enabled states when it
+/// goes out of scope.
+///
+/// This is created by [`Device::enable_channels_scoped()`].
+#[derive(Debug)]
+pub struct ChannelGuard {
+    /// The channels that were enabled, with their previous states
+    prev: Vec<(Channel, bool)>,
+}
+
+impl ChannelGuard {
+    /// Gets an iterator over the channels that the guard enabled.
+    pub fn channels(&self) -> impl Iterator<Item = &Channel> + '_ {
+        self.prev.iter().map(|(ch, _)| ch)
+    }
+}
+
+impl Drop for ChannelGuard {
+    /// Dropping the guard restores the previous enabled states.
+    fn drop(&mut self) {
+        // Restore in reverse, in case an ID was given more than once.
+        for (ch, enabled) in self.prev.iter().rev() {
+            if !enabled {
+                ch.disable();
+            }
+        }
+    }
+}
+
+/// Restores the trigger of a device when dropped.
+/// Used by [`Device::with_trigger_disabled()`].
+struct TriggerRestore<'a> {
+    /// The device to restore
+    dev: &'a Device,
+    /// The trigger to restore, until it's done
+    trig: Option<Device>,
+}
+
+impl<'a> TriggerRestore<'a> {
+    /// Restores the trigger, if it hasn't been already.
+    fn restore(&mut self) -> Result<()> {
+        match self.trig.take() {
+            Some(trig) => self.dev.set_trigger(&trig),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Drop for TriggerRestore<'a> {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
 /// Iterator over the Channels in a Device
 #[derive(Debug)]
 pub struct ChannelIterator<'a> {
@@ -346,8 +1543,17 @@ impl<'a> Iterator for ChannelIterator<'a> {
             Err(_) => None,
         }
     }
+
+    /// The number of remaining channels is known up front, since the library
+    /// can't fail to look one up by an index below the count.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.dev.num_channels().saturating_sub(self.idx);
+        (n, Some(n))
+    }
 }
 
+impl<'a> ExactSizeIterator for ChannelIterator<'a> {}
+
 /// Iterator over the attributes in a Device
 #[derive(Debug)]
 pub struct AttrIterator<'a> {
@@ -383,6 +1589,179 @@ impl<'a> Iterator for AttrIterator<'a> {
 mod tests {
     use super::*;
 
+    // The namespaced attribute functions should match the specific ones
+    #[test]
+    fn attr_namespace() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+
+        let n = dev.attr_count(AttrNamespace::Device);
+        assert_eq!(n, dev.num_attrs());
+
+        let name = dev.attr_name(AttrNamespace::Device, 0).unwrap();
+        assert_eq!(name, dev.get_attr(0).unwrap());
+
+        let val: String = dev.attr_read_ns(AttrNamespace::Device, &name).unwrap();
+        assert_eq!(val, dev.attr_read_str(&name).unwrap());
+    }
+
+    // The raw bytes of a text attribute should be the whole sysfs value
+    #[test]
+    fn attr_read_raw_bytes() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+        let name = dev.get_attr(0).unwrap();
+
+        // The library strips the newline from the end of a sysfs value.
+        let sval = dev.attr_read_str(&name).unwrap();
+        let mut buf = Vec::new();
+        let n = dev.attr_read_raw_bytes(&name, &mut buf).unwrap();
+        assert_eq!(n, buf.len());
+        assert_eq!(buf, format!("{}\n", sval).into_bytes());
+    }
+
+    // Waiting on an attribute should succeed or time out
+    #[test]
+    fn wait_for_attr() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+        let name = dev.get_attr(0).unwrap();
+        let timeout = Duration::from_millis(50);
+
+        assert!(dev.wait_for_attr(&name, |_| true, timeout).is_ok());
+        assert!(matches!(
+            dev.wait_for_attr(&name, |_| false, timeout),
+            Err(Error::Timeout)
+        ));
+    }
+
+    // Toggling a channel should show up as a change from the snapshot
+    #[test]
+    fn channels_changed_since() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        let snapshot = dev.channel_enable_states();
+        assert!(dev.channels_changed_since(&snapshot).is_empty());
+
+        let (idx, chan) = dev
+            .channels()
+            .enumerate()
+            .find(|(_, ch)| ch.is_scan_element())
+            .unwrap();
+        let enabled = chan.is_enabled();
+        if enabled {
+            chan.disable();
+        }
+        else {
+            chan.enable();
+        }
+        assert_eq!(dev.channels_changed_since(&snapshot), vec![idx]);
+
+        if enabled {
+            chan.enable();
+        }
+        else {
+            chan.disable();
+        }
+    }
+
+    // The dummy device has an X-axis accelerometer channel
+    #[test]
+    fn find_channel_by() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+
+        let chan = dev.find_channel_by(ChannelType::Accel, Some(Modifier::X), false);
+        assert_eq!(chan.unwrap().modifier(), Modifier::X);
+        assert!(dev
+            .find_channel_by(ChannelType::Accel, Some(Modifier::Z), false)
+            .is_none());
+        assert!(dev
+            .find_channel_by(ChannelType::Voltage, None, false)
+            .is_some());
+    }
+
+    // The guard should restore the channels when dropped
+    #[test]
+    fn enable_channels_scoped() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        let chan = dev.scan_channels().next().unwrap();
+        let id = chan.id().unwrap();
+
+        chan.disable();
+        {
+            let guard = dev.enable_channels_scoped(&[&id]).unwrap();
+            assert_eq!(guard.channels().count(), 1);
+            assert!(chan.is_enabled());
+        }
+        assert!(!chan.is_enabled());
+        assert!(dev.enable_channels_scoped(&["no_such_channel"]).is_err());
+    }
+
+    // Measuring a subset should leave the enabled channels unchanged
+    #[test]
+    fn sample_size_for() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        let chan = dev.scan_channels().next().unwrap();
+
+        let states = dev.channel_enable_states();
+        let sz = dev.sample_size_for(&[&chan]).unwrap();
+        assert_eq!(sz, chan.data_format().byte_length());
+        assert_eq!(dev.channel_enable_states(), states);
+    }
+
+    // A device without triaxial channels can't read a vector
+    #[test]
+    fn read_vector3_missing() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        assert!(dev.read_vector3(ChannelType::Magn).is_err());
+    }
+
+    // Watching an attribute should send its current value
+    #[test]
+    fn watch_attr() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+        let name = dev.get_attr(0).unwrap();
+
+        let rx = dev.watch_attr(&name, Duration::from_millis(10)).unwrap();
+        let val = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(val, dev.attr_read_str(&name).unwrap());
+    }
+
+    // The timestamp clock names should round-trip
+    #[test]
+    fn timestamp_clock_names() {
+        for clock in &[TimestampClock::Realtime, TimestampClock::MonotonicRaw] {
+            let name = clock.to_string();
+            assert_eq!(name.parse::<TimestampClock>().unwrap(), *clock);
+        }
+        assert_eq!(
+            "boottime\n".parse::<TimestampClock>().unwrap(),
+            TimestampClock::Boottime
+        );
+        assert!("sundial".parse::<TimestampClock>().is_err());
+    }
+
+    // Check the alignment of the elements in a sample
+    #[test]
+    fn sample_offsets() {
+        let (offsets, n) = scan_offsets(&[2, 2, 2, 8]);
+        assert_eq!(offsets, vec![0, 2, 4, 8]);
+        assert_eq!(n, 16);
+
+        let (offsets, n) = scan_offsets(&[1, 4, 2]);
+        assert_eq!(offsets, vec![0, 4, 8]);
+        assert_eq!(n, 12);
+
+        let (offsets, n) = scan_offsets(&[]);
+        assert!(offsets.is_empty());
+        assert_eq!(n, 0);
+    }
+
     // Make sure we get a device
     #[test]
     fn get_device() {
@@ -405,4 +1784,33 @@ mod tests {
         assert!(n != 0);
         assert!(dev.attributes().count() == n);
     }
+
+    // See that the trigger is reported, and "no trigger" isn't an error.
+    // This requires the dummy device and hrtimer from `load_dummy.sh`.
+    #[test]
+    fn trigger() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device("dummydev").unwrap();
+        let trig = ctx.find_device("timer0").unwrap();
+
+        dev.remove_trigger().unwrap();
+        assert!(dev.trigger().unwrap().is_none());
+
+        dev.set_trigger(&trig).unwrap();
+        assert!(dev.trigger().unwrap() == Some(trig));
+
+        dev.remove_trigger().unwrap();
+        assert!(dev.trigger().unwrap().is_none());
+    }
+
+    // See that the channel iterator reports the exact number of channels
+    #[test]
+    fn channel_iterator_len() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+
+        let n = dev.num_channels();
+        assert_eq!(dev.channels().len(), n);
+        assert_eq!(dev.channels().count(), n);
+    }
 }
@@ -10,7 +10,8 @@
 //!
 //! Error definitions for the Industrial I/O Library.
 
-use std::{ffi, io};
+use nix::errno::Errno;
+use std::{ffi, io, str};
 use thiserror::Error;
 
 //type SysError = nix::Error::Sys;
@@ -33,12 +34,51 @@ pub enum Error {
     /// The wrong data type used in an operation
     #[error("Wrong data type")]
     WrongDataType,
+    /// The size of a sample type doesn't match the storage size of a
+    /// channel's samples.
+    #[error(
+        "Wrong sample size: the type is {type_size} bytes, but the channel's samples are \
+         {sample_size} bytes"
+    )]
+    SampleSizeMismatch {
+        /// The size of the requested type, in bytes
+        type_size: usize,
+        /// The storage size of the channel's samples, in bytes
+        sample_size: usize,
+    },
     /// The size of a data or return value was different than expected.
     #[error("Bad return size")]
     BadReturnSize,
     /// A device or channel index did not find a requested object
     #[error("Invalid index")]
     InvalidIndex,
+    /// A context could not be created for the specified URI
+    #[error("Unable to create context for '{uri}': {msg}")]
+    ContextCreation {
+        /// The URI of the context that could not be created
+        uri: String,
+        /// The system error reported when creating the context
+        errno: Errno,
+        /// The library's description of the error
+        msg: String,
+    },
+    /// Debug access, such as reading or writing registers, isn't available
+    /// for the device.
+    #[error(
+        "Debug access not available ({0}). This requires root privileges and a backend with \
+         debugfs support"
+    )]
+    DebugNotAvailable(Errno),
+    /// A buffer couldn't be created because the device is busy, usually
+    /// because it already has a buffer or an open event stream.
+    #[error(
+        "The device is busy ({0}). It may already have a buffer, as only one is allowed per \
+         device, or an open event stream"
+    )]
+    DeviceBusy(Errno),
+    /// An operation did not complete in the time allowed
+    #[error("Timed out")]
+    Timeout,
     /// A generic error with a string explaination
     #[error("{0}")]
     General(String),
@@ -47,9 +87,43 @@ pub enum Error {
 /// The default result type for the IIO library
 pub type Result<T> = std::result::Result<T, Error>;
 
-impl From<nix::errno::Errno> for Error {
+impl Error {
+    /// Gets the system error number, if this is a system error.
+    pub fn errno(&self) -> Option<Errno> {
+        match *self {
+            Error::Nix(nix::Error::Sys(err)) => Some(err),
+            Error::ContextCreation { errno, .. } => Some(errno),
+            Error::DebugNotAvailable(err) => Some(err),
+            Error::DeviceBusy(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Determines if this is a transient error, such that the operation
+    /// might succeed if retried.
+    ///
+    /// These are the system errors ETIMEDOUT, EAGAIN, and ECONNRESET, which
+    /// typically come from a network connection that is momentarily slow
+    /// or dropped.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.errno(),
+            Some(Errno::ETIMEDOUT) | Some(Errno::EAGAIN) | Some(Errno::ECONNRESET)
+        )
+    }
+}
+
+impl From<str::Utf8Error> for Error {
+    /// Converts a failed UTF-8 conversion of a string from the C library
+    /// into a string conversion error.
+    fn from(_err: str::Utf8Error) -> Self {
+        Error::StringConversionError
+    }
+}
+
+impl From<Errno> for Error {
     /// Converts a *nix errno into an Error
-    fn from(err: nix::errno::Errno) -> Self {
+    fn from(err: Errno) -> Self {
         nix::Error::Sys(err).into()
     }
 }
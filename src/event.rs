@@ -0,0 +1,245 @@
+// libiio-sys/src/event.rs
+//
+// Copyright (c) 2018-2021, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Industrial I/O Events
+//!
+//! IIO devices can report events, such as a threshold being crossed, as
+//! distinct from the samples that are captured into a buffer. The version
+//! of libiio that this crate uses doesn't have an event interface, so
+//! events are read directly from the kernel's event file descriptor. This
+//! only works for devices in a local context.
+//!
+
+use super::*;
+use std::{
+    fs::File,
+    io::Read,
+    mem,
+    os::{raw::c_int, unix::io::AsRawFd, unix::io::FromRawFd},
+};
+
+// The kernel ioctl, kept private to the module.
+mod ioctl {
+    use std::os::raw::c_int;
+
+    nix::ioctl_read!(
+        /// Gets the event file descriptor from a device's character device.
+        /// This is the kernel's `IIO_GET_EVENT_FD_IOCTL`.
+        iio_get_event_fd,
+        b'i',
+        0x90,
+        c_int
+    );
+}
+use ioctl::iio_get_event_fd;
+
+/// The size of an event record from the kernel: a u64 ID and i64 timestamp.
+const EVENT_DATA_SIZE: usize = 16;
+
+/// The type of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    /// A value crossed a threshold
+    Threshold,
+    /// The magnitude of a value crossed a threshold
+    Magnitude,
+    /// The rate of change of a value crossed a threshold
+    RateOfChange,
+    /// A value crossed an adaptive threshold
+    ThresholdAdaptive,
+    /// The magnitude of a value crossed an adaptive threshold
+    MagnitudeAdaptive,
+    /// A value changed
+    Change,
+    /// The magnitude of a value, relative to a reference, crossed a threshold
+    MagnitudeReferenced,
+    /// A gesture was detected
+    Gesture,
+    /// An event type not known to this library
+    Unknown(u8),
+}
+
+impl From<u8> for EventType {
+    fn from(n: u8) -> Self {
+        use EventType::*;
+        match n {
+            0 => Threshold,
+            1 => Magnitude,
+            2 => RateOfChange,
+            3 => ThresholdAdaptive,
+            4 => MagnitudeAdaptive,
+            5 => Change,
+            6 => MagnitudeReferenced,
+            7 => Gesture,
+            _ => Unknown(n),
+        }
+    }
+}
+
+/// The direction of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventDirection {
+    /// Either direction
+    Either,
+    /// The value was rising
+    Rising,
+    /// The value was falling
+    Falling,
+    /// The event has no direction
+    None,
+    /// An event direction not known to this library
+    Unknown(u8),
+}
+
+impl From<u8> for EventDirection {
+    fn from(n: u8) -> Self {
+        use EventDirection::*;
+        match n {
+            0 => Either,
+            1 => Rising,
+            2 => Falling,
+            3 => None,
+            _ => Unknown(n),
+        }
+    }
+}
+
+/// An event reported by a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    /// The raw event code from the kernel
+    pub id: u64,
+    /// The type of the channel that produced the event
+    pub chan_type: ChannelType,
+    /// The channel number
+    pub channel: i16,
+    /// The second channel number, for differential channels
+    pub channel2: i16,
+    /// The channel modifier, such as an axis, as defined by the kernel
+    pub modifier: u8,
+    /// Whether the event came from a differential channel
+    pub differential: bool,
+    /// The type of the event
+    pub event_type: EventType,
+    /// The direction of the event
+    pub direction: EventDirection,
+    /// The time of the event, in nanoseconds
+    pub timestamp: i64,
+}
+
+impl Event {
+    /// Decodes an event from the kernel's event code and timestamp.
+    fn new(id: u64, timestamp: i64) -> Self {
+        let chan_type = ((id >> 32) & 0xFF) as u32;
+        let chan_type = if chan_type <= ffi::iio_chan_type_IIO_GRAVITY {
+            // The channel type enum is contiguous up to Gravity.
+            unsafe { mem::transmute::<u32, ChannelType>(chan_type) }
+        }
+        else {
+            ChannelType::Unknown
+        };
+
+        Self {
+            id,
+            chan_type,
+            channel: (id & 0xFFFF) as i16,
+            channel2: ((id >> 16) & 0xFFFF) as i16,
+            modifier: ((id >> 40) & 0xFF) as u8,
+            differential: (id >> 55) & 0x01 != 0,
+            event_type: EventType::from(((id >> 56) & 0xFF) as u8),
+            direction: EventDirection::from(((id >> 48) & 0x7F) as u8),
+            timestamp,
+        }
+    }
+}
+
+/// A stream of events from a device.
+///
+/// This is created with [`Device::event_stream()`]. Reads from the stream
+/// block until an event is available.
+#[derive(Debug)]
+pub struct EventStream {
+    /// The event file descriptor from the kernel
+    file: File,
+}
+
+impl EventStream {
+    /// Opens the event stream for the device.
+    pub(crate) fn new(dev: &Device) -> Result<Self> {
+        if dev.context().name() != "local" {
+            return Err(Error::General(
+                "Events are only available for devices in a local context".into(),
+            ));
+        }
+        let id = dev
+            .id()
+            .ok_or_else(|| Error::General("The device has no ID".into()))?;
+
+        let chrdev = File::open(format!("/dev/{}", id))?;
+        let mut fd: c_int = -1;
+        unsafe { iio_get_event_fd(chrdev.as_raw_fd(), &mut fd) }?;
+
+        let file = unsafe { File::from_raw_fd(fd) };
+        Ok(Self { file })
+    }
+
+    /// Reads the next event, blocking until one is available.
+    pub fn read_event(&mut self) -> Result<Event> {
+        let mut buf = [0u8; EVENT_DATA_SIZE];
+        self.file.read_exact(&mut buf)?;
+
+        let mut id = [0u8; 8];
+        let mut ts = [0u8; 8];
+        id.copy_from_slice(&buf[..8]);
+        ts.copy_from_slice(&buf[8..]);
+        Ok(Event::new(u64::from_ne_bytes(id), i64::from_ne_bytes(ts)))
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = Result<Event>;
+
+    /// Reads the next event, blocking until one is available.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_event())
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decode a rising threshold event on voltage channel 3
+    #[test]
+    fn decode_event() {
+        let id = (1u64 << 48) | 3;
+        let ev = Event::new(id, 1234);
+        assert_eq!(ev.chan_type, ChannelType::Voltage);
+        assert_eq!(ev.channel, 3);
+        assert_eq!(ev.channel2, 0);
+        assert!(!ev.differential);
+        assert_eq!(ev.event_type, EventType::Threshold);
+        assert_eq!(ev.direction, EventDirection::Rising);
+        assert_eq!(ev.timestamp, 1234);
+
+        let id = (2u64 << 56) | (1u64 << 55) | (2u64 << 48) | (9u64 << 32) | (1 << 16);
+        let ev = Event::new(id, 0);
+        assert_eq!(ev.chan_type, ChannelType::Temp);
+        assert_eq!(ev.channel, 0);
+        assert_eq!(ev.channel2, 1);
+        assert!(ev.differential);
+        assert_eq!(ev.event_type, EventType::RateOfChange);
+        assert_eq!(ev.direction, EventDirection::Falling);
+
+        let ev = Event::new(200u64 << 32, 0);
+        assert_eq!(ev.chan_type, ChannelType::Unknown);
+    }
+}
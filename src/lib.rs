@@ -35,6 +35,7 @@
 
 use std::{
     any::{Any, TypeId},
+    cmp::Ordering,
     collections::HashMap,
     ffi::{CStr, CString},
     fmt,
@@ -51,6 +52,8 @@ pub use crate::channel::*;
 pub use crate::context::*;
 pub use crate::device::*;
 pub use crate::errors::*;
+pub use crate::event::*;
+pub use crate::stream::*;
 
 mod macros;
 
@@ -59,6 +62,8 @@ pub mod channel;
 pub mod context;
 pub mod device;
 pub mod errors;
+pub mod event;
+pub mod stream;
 
 /// According to the IIO samples, internal buffers need to be big enough
 /// for attributes coming back from the kernel.
@@ -79,6 +84,27 @@ fn cstring_opt(pstr: *const c_char) -> Option<String> {
     }
 }
 
+/// Looks up a function in the linked C library by name.
+///
+/// This is used for functions that are only available in newer versions of
+/// libiio than the one the bindings were generated from. It returns `None`
+/// if the linked library doesn't have the function.
+pub(crate) fn lib_fn(name: &[u8]) -> Option<*mut c_void> {
+    let name = CStr::from_bytes_with_nul(name).ok()?;
+    let pfn = unsafe { nix::libc::dlsym(nix::libc::RTLD_DEFAULT, name.as_ptr()) };
+    match pfn.is_null() {
+        true => None,
+        false => Some(pfn),
+    }
+}
+
+/// Gets the library's description of a system error.
+pub(crate) fn strerror(err: errno::Errno) -> String {
+    let mut buf = vec![0 as c_char; 256];
+    unsafe { ffi::iio_strerror(err as c_int, buf.as_mut_ptr(), buf.len()) };
+    cstring_opt(buf.as_ptr()).unwrap_or_default()
+}
+
 pub(crate) fn sys_result<T>(ret: i32, result: T) -> Result<T> {
     if ret < 0 {
         Err(errno::from_i32(-ret).into())
@@ -88,6 +114,24 @@ pub(crate) fn sys_result<T>(ret: i32, result: T) -> Result<T> {
     }
 }
 
+/// Converts the return value of a debug operation, like a register read,
+/// into a result. The errors that indicate that debug access isn't
+/// available for the device are reported as `Error::DebugNotAvailable`.
+pub(crate) fn debug_result<T>(ret: i32, result: T) -> Result<T> {
+    use errno::Errno::*;
+
+    if ret < 0 {
+        let err = errno::from_i32(-ret);
+        match err {
+            EACCES | EPERM | ENOSYS => Err(Error::DebugNotAvailable(err)),
+            _ => Err(err.into()),
+        }
+    }
+    else {
+        Ok(result)
+    }
+}
+
 /// Converts the attribute name and value to CString's that can be sent to
 /// the C library.
 ///
@@ -114,12 +158,34 @@ where
     T: FromStr + Any,
 {
     if TypeId::of::<T>() == TypeId::of::<bool>() {
-        sval = (if sval.trim() == "0" { "false" } else { "true" }).into();
+        sval = parse_bool(&sval)?.to_string();
     }
     let val = T::from_str(&sval).map_err(|_| Error::StringConversionError)?;
     Ok(val)
 }
 
+/// Parses a boolean attribute value.
+///
+/// Devices encode booleans in different ways, so this accepts any integer
+/// (non-zero is true) and, ignoring case, "true"/"false", "y"/"n",
+/// "yes"/"no", and "on"/"off".
+pub(crate) fn parse_bool(sval: &str) -> Result<bool> {
+    let sval = sval.trim();
+    if let Ok(val) = sval.parse::<i64>() {
+        return Ok(val != 0);
+    }
+    parse_bool_keyword(sval).ok_or(Error::StringConversionError)
+}
+
+// Parses one of the keywords used to encode a boolean, ignoring case.
+fn parse_bool_keyword(sval: &str) -> Option<bool> {
+    match sval.to_lowercase().as_str() {
+        "true" | "y" | "yes" | "on" => Some(true),
+        "false" | "n" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 // Callback from the C lib to extract the collection of all
 // device-specific attributes. See attr_read_all().
 pub(crate) unsafe extern "C" fn attr_read_all_cb(
@@ -162,6 +228,151 @@ impl fmt::Display for Version {
 
 // --------------------------------------------------------------------------
 
+/// An attribute value with its type inferred from its string form.
+///
+/// The type is detected by trying, in order:
+///
+/// - an integer, like "42" or "-1"
+/// - a floating-point number, like "0.5" or "1e-3"
+/// - a boolean keyword, ignoring case: "true", "false", "y", "n", "yes",
+///   "no", "on", or "off"
+///
+/// If none of those match, the value is kept as a string. Note that "1"
+/// and "0" are detected as integers, not booleans. Surrounding whitespace
+/// is ignored for the detection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    /// A boolean value
+    Bool(bool),
+    /// An integer value
+    Int(i64),
+    /// A floating-point value
+    Float(f64),
+    /// A value that couldn't be parsed as any other type
+    Str(String),
+}
+
+impl From<&str> for AttrValue {
+    /// Creates a value, detecting its type from the string.
+    fn from(s: &str) -> Self {
+        let sval = s.trim();
+        if let Ok(val) = sval.parse::<i64>() {
+            AttrValue::Int(val)
+        }
+        else if let Ok(val) = sval.parse::<f64>() {
+            AttrValue::Float(val)
+        }
+        else {
+            match parse_bool_keyword(sval) {
+                Some(val) => AttrValue::Bool(val),
+                None => AttrValue::Str(s.to_string()),
+            }
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// The values that an attribute supports, as reported by its companion
+/// `<attr>_available` attribute.
+///
+/// The kernel reports these either as a list of discrete values, like
+/// "1 10 100", or as a range in the form "[min step max]".
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvailableValues {
+    /// A list of discrete values
+    List(Vec<f64>),
+    /// A range of values
+    Range {
+        /// The minimum value
+        min: f64,
+        /// The increment between values
+        step: f64,
+        /// The maximum value
+        max: f64,
+    },
+}
+
+impl AvailableValues {
+    /// Gets the supported value closest to the requested one.
+    ///
+    /// This returns `None` if there are no values in a list.
+    pub fn nearest(&self, val: f64) -> Option<f64> {
+        match *self {
+            AvailableValues::List(ref vals) => vals.iter().copied().min_by(|a, b| {
+                let (da, db) = ((a - val).abs(), (b - val).abs());
+                da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+            }),
+            AvailableValues::Range { min, step, max } => {
+                let mut v = val.max(min).min(max);
+                if step > 0.0 {
+                    v = (min + ((v - min) / step).round() * step).min(max);
+                }
+                Some(v)
+            }
+        }
+    }
+}
+
+impl FromStr for AvailableValues {
+    type Err = Error;
+
+    /// Parses the value of an `_available` attribute.
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |s: &str| -> Result<Vec<f64>> {
+            s.split_whitespace()
+                .map(|v| v.parse::<f64>().map_err(|_| Error::StringConversionError))
+                .collect()
+        };
+
+        let s = s.trim();
+        if s.starts_with('[') && s.ends_with(']') {
+            match parse(&s[1..s.len() - 1])?.as_slice() {
+                &[min, step, max] => Ok(AvailableValues::Range { min, step, max }),
+                _ => Err(Error::StringConversionError),
+            }
+        }
+        else {
+            Ok(AvailableValues::List(parse(s)?))
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// Uniform access to the attributes of a context, device, or channel.
+///
+/// This allows generic tools, like configuration dumpers and editors, to
+/// handle any of the objects with a single routine. The string-based
+/// attribute methods of the types, like `attr_read_str()`, come from their
+/// implementations of this trait. Where a type also has an inherent method
+/// of the same name, like the generic `attr_read()`, the inherent method
+/// is used when called on the type directly, and the trait method in
+/// generic code.
+pub trait Attributes {
+    /// Gets the number of attributes
+    fn num_attrs(&self) -> usize;
+
+    /// Gets the name of the attribute at the index
+    fn attr_name(&self, idx: usize) -> Result<String>;
+
+    /// Reads the value of an attribute as a string
+    fn attr_read(&self, name: &str) -> Result<String>;
+
+    /// Writes the value of an attribute as a string, returning the number
+    /// of bytes written
+    fn attr_write(&self, name: &str, val: &str) -> Result<usize>;
+
+    /// Gets the names of all the attributes
+    fn attr_names(&self) -> Vec<String> {
+        (0..self.num_attrs())
+            .filter_map(|idx| self.attr_name(idx).ok())
+            .collect()
+    }
+}
+
+// --------------------------------------------------------------------------
+
 /// Gets the library version as (Major, Minor, Git Tag)
 pub fn library_version() -> Version {
     let mut major: c_uint = 0;
@@ -218,6 +429,115 @@ mod tests {
         assert_eq!(&val, "hello");
     }
 
+    // The trait should give the same attributes as the concrete methods.
+    #[test]
+    fn attributes_trait() {
+        fn names<A: Attributes>(obj: &A) -> Vec<String> {
+            obj.attr_names()
+        }
+
+        let ctx = Context::new().unwrap();
+        let dev = ctx.get_device(0).unwrap();
+        assert_eq!(names(&dev), dev.attributes().collect::<Vec<_>>());
+
+        let name = Attributes::attr_name(&dev, 0).unwrap();
+        assert_eq!(name, dev.get_attr(0).unwrap());
+        assert_eq!(
+            Attributes::attr_read(&dev, &name).unwrap(),
+            dev.attr_read_str(&name).unwrap()
+        );
+        assert!(ctx.attr_write("x", "y").is_err());
+    }
+
+    #[test]
+    fn parse_bool_encodings() {
+        for sval in &["1", "-1", "true", "TRUE", "Y", "y", "yes", "on", " 1\n"] {
+            assert!(parse_bool(sval).unwrap(), "{:?}", sval);
+        }
+        for sval in &["0", "false", "False", "N", "n", "no", "off", "0\n"] {
+            assert!(!parse_bool(sval).unwrap(), "{:?}", sval);
+        }
+        assert!(parse_bool("").is_err());
+        assert!(parse_bool("maybe").is_err());
+
+        let val: bool = string_to_attr("N".to_string()).unwrap();
+        assert!(!val);
+    }
+
+    #[test]
+    fn error_conversions() {
+        fn nul() -> Result<CString> {
+            Ok(CString::new("a\0b")?)
+        }
+        fn utf8(bytes: &[u8]) -> Result<&str> {
+            Ok(str::from_utf8(bytes)?)
+        }
+        fn sys() -> Result<()> {
+            Err(nix::Error::Sys(errno::Errno::EIO))?
+        }
+        assert!(matches!(nul(), Err(Error::NulError(_))));
+        assert!(matches!(utf8(&[0xFF]), Err(Error::StringConversionError)));
+        assert_eq!(sys().unwrap_err().errno(), Some(errno::Errno::EIO));
+    }
+
+    #[test]
+    fn debug_result_errors() {
+        let ret = -(errno::Errno::EACCES as i32);
+        assert!(matches!(
+            debug_result(ret, ()),
+            Err(Error::DebugNotAvailable(errno::Errno::EACCES))
+        ));
+
+        let ret = -(errno::Errno::EIO as i32);
+        assert!(matches!(debug_result(ret, ()), Err(Error::Nix(_))));
+
+        let ret = -(errno::Errno::ENOENT as i32);
+        assert_eq!(
+            debug_result(ret, ()).unwrap_err().errno(),
+            Some(errno::Errno::ENOENT)
+        );
+        assert_eq!(debug_result(0, 42).unwrap(), 42);
+    }
+
+    #[test]
+    fn attr_value_detect() {
+        assert_eq!(AttrValue::from("42\n"), AttrValue::Int(42));
+        assert_eq!(AttrValue::from("-1"), AttrValue::Int(-1));
+        assert_eq!(AttrValue::from("0.125"), AttrValue::Float(0.125));
+        assert_eq!(AttrValue::from("True"), AttrValue::Bool(true));
+        assert_eq!(AttrValue::from("N"), AttrValue::Bool(false));
+        assert_eq!(AttrValue::from("on"), AttrValue::Bool(true));
+        assert_eq!(AttrValue::from("OFF\n"), AttrValue::Bool(false));
+        assert_eq!(
+            AttrValue::from("normal"),
+            AttrValue::Str("normal".to_string())
+        );
+    }
+
+    #[test]
+    fn available_values() {
+        let vals: AvailableValues = "1 10 100\n".parse().unwrap();
+        assert_eq!(vals, AvailableValues::List(vec![1.0, 10.0, 100.0]));
+        assert_eq!(vals.nearest(30.0), Some(10.0));
+        assert_eq!(vals.nearest(1000.0), Some(100.0));
+
+        let vals: AvailableValues = "[0 5 100]".parse().unwrap();
+        assert_eq!(
+            vals,
+            AvailableValues::Range {
+                min: 0.0,
+                step: 5.0,
+                max: 100.0
+            }
+        );
+        assert_eq!(vals.nearest(12.0), Some(10.0));
+        assert_eq!(vals.nearest(-3.0), Some(0.0));
+        assert_eq!(vals.nearest(200.0), Some(100.0));
+
+        assert!("[1 2]".parse::<AvailableValues>().is_err());
+        assert!("1 two 3".parse::<AvailableValues>().is_err());
+    }
+
     #[test]
     fn attr_val_to_string() {
         let s = attr_to_string(123).unwrap();
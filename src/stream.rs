@@ -0,0 +1,721 @@
+// libiio-sys/src/stream.rs
+//
+// Copyright (c) 2018-2021, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Industrial I/O Streams
+//!
+//! A stream wraps a [`Buffer`] for continuous capture, where the buffer is
+//! refilled repeatedly, and keeps track of state across the windows of
+//! samples from each refill. An output stream does the same for continuous
+//! output, where the buffer is filled and pushed repeatedly.
+//!
+
+use super::*;
+use crate::{device::SAMPLING_FREQ_ATTR, ffi};
+use std::{any::TypeId, slice};
+
+/// A continuous stream of input samples from a device.
+///
+/// The stream owns the [`Buffer`] that is used for the capture. Each call
+/// to [`InputStream::refill()`] gets the next window of samples, which can
+/// then be read from the buffer.
+#[derive(Debug)]
+pub struct InputStream {
+    /// The buffer used for the capture
+    buf: Buffer,
+    /// The last value of the counter used to detect dropped samples
+    last_count: Option<u64>,
+}
+
+/// A report of the samples that were dropped in a window of input.
+///
+/// See [`InputStream::detect_drops()`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DropReport {
+    /// The positions, as sample indexes into the current window, at which
+    /// discontinuities were detected. A position of zero means that the
+    /// gap was between the previous window and this one.
+    pub positions: Vec<usize>,
+    /// The total number of samples that were missed.
+    pub missed: u64,
+}
+
+impl DropReport {
+    /// Gets the number of discontinuities that were detected.
+    pub fn num_drops(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Determines if no samples were dropped.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+impl InputStream {
+    /// Creates an input stream from a buffer.
+    pub fn new(buf: Buffer) -> Self {
+        Self {
+            buf,
+            last_count: None,
+        }
+    }
+
+    /// Gets a reference to the buffer used by the stream.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buf
+    }
+
+    /// Gets a mutable reference to the buffer used by the stream.
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+
+    /// Consumes the stream, returning the underlying buffer.
+    pub fn into_buffer(self) -> Buffer {
+        self.buf
+    }
+
+    /// Gets the next window of samples from the hardware.
+    ///
+    /// On success, this returns the number of bytes read.
+    pub fn refill(&mut self) -> Result<usize> {
+        self.buf.refill()
+    }
+
+    /// Checks the current window for dropped samples, using a counter.
+    ///
+    /// For devices that have a channel with a monotonic sequence count,
+    /// this checks that each sample in the window has the count following
+    /// that of the previous sample, and reports any gaps. The count is
+    /// tracked across windows, so this should be called once after each
+    /// [`InputStream::refill()`]. The counter is expected to wrap around
+    /// at the number of bits in the channel.
+    ///
+    /// A step forward of up to half the range of the counter is taken as a
+    /// gap, with the samples in between missed. Any other step, such as a
+    /// repeated value or one that goes backward, is taken as a reset of the
+    /// counter. Its position is reported, but no samples are counted as
+    /// missed.
+    ///
+    /// If the channel isn't part of the buffer, an empty report is
+    /// returned.
+    pub fn detect_drops(&mut self, counter_chan: &Channel) -> DropReport {
+        let mut report = DropReport::default();
+
+        let counts = match raw_samples(&self.buf, counter_chan) {
+            Some(counts) => counts,
+            None => return report,
+        };
+
+        let bits = counter_chan.data_format().bits();
+        let mask = match bits {
+            1..=63 => (1u64 << bits) - 1,
+            _ => u64::MAX,
+        };
+
+        for (i, count) in counts.into_iter().enumerate() {
+            let count = (count as u64) & mask;
+            if let Some(last) = self.last_count {
+                if let Some(missed) = count_step(last, count, mask) {
+                    report.positions.push(i);
+                    report.missed = report.missed.saturating_add(missed);
+                }
+            }
+            self.last_count = Some(count);
+        }
+        report
+    }
+}
+
+// Checks the step between two successive values of a counter that wraps
+// at `mask`. This returns None if the step is by exactly one, otherwise
+// the number of missed samples for a gap, or zero for a reset.
+fn count_step(last: u64, count: u64, mask: u64) -> Option<u64> {
+    let step = count.wrapping_sub(last) & mask;
+    match step {
+        1 => None,
+        n if n != 0 && n <= mask / 2 + 1 => Some(n - 1),
+        _ => Some(0),
+    }
+}
+
+/// A reader that provides samples from a channel in chunks of any size,
+/// regardless of the size of the buffer.
+///
+/// When processing windows of samples that don't evenly divide the
+/// buffer depth, a window can straddle two refills. This reader keeps
+/// the leftover samples from each refill in a staging area, and refills
+/// the buffer as needed, so that each request is handed out as a single
+/// contiguous slice and no samples are dropped at refill boundaries.
+#[derive(Debug)]
+pub struct ContinuousReader<T> {
+    /// The stream with the buffer to read from
+    stream: InputStream,
+    /// The channel to read
+    chan: Channel,
+    /// The samples that were read from the buffer but not yet consumed
+    staged: Vec<T>,
+    /// The number of staged samples handed out by the last read
+    consumed: usize,
+}
+
+impl<T> ContinuousReader<T>
+where
+    T: Default + Copy + 'static,
+{
+    /// Creates a reader for a channel of the stream.
+    ///
+    /// The channel must be enabled in the stream's buffer, and `T` must
+    /// match the channel's sample type.
+    pub fn new(stream: InputStream, chan: &Channel) -> Result<Self> {
+        validate_sample_type::<T>(&chan.data_format())?;
+        if chan.type_of() != Some(TypeId::of::<T>()) {
+            return Err(Error::WrongDataType);
+        }
+        Ok(Self {
+            stream,
+            chan: chan.clone(),
+            staged: Vec::new(),
+            consumed: 0,
+        })
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn stream(&self) -> &InputStream {
+        &self.stream
+    }
+
+    /// Consumes the reader, returning the underlying stream.
+    ///
+    /// Any samples that were staged but not yet read are discarded.
+    pub fn into_stream(self) -> InputStream {
+        self.stream
+    }
+
+    /// Gets the number of samples that are staged, and can be read without
+    /// refilling the buffer.
+    pub fn available(&self) -> usize {
+        self.staged.len() - self.consumed
+    }
+
+    /// Reads the next `n` samples from the channel, converted to the host
+    /// format.
+    ///
+    /// This refills the buffer as many times as needed to get the samples.
+    /// The returned slice is valid until the next read.
+    pub fn read(&mut self, n: usize) -> Result<&[T]> {
+        self.staged.drain(..self.consumed);
+        self.consumed = 0;
+
+        while self.staged.len() < n {
+            self.stream.refill()?;
+            let v = self.chan.read::<T>(self.stream.buffer())?;
+            self.staged.extend(v);
+        }
+        self.consumed = n;
+        Ok(&self.staged[..n])
+    }
+}
+
+/// A continuous stream of output samples to a device.
+///
+/// This is the output counterpart to [`InputStream`]. Rather than a cyclic
+/// buffer, which repeats a fixed waveform, the application keeps feeding
+/// new windows of samples with [`OutputStream::write_window()`], which
+/// converts them from physical units to the hardware format, fills the
+/// buffer, and pushes it.
+#[derive(Debug)]
+pub struct OutputStream {
+    /// The buffer used for the output
+    buf: Buffer,
+    /// The output channels of the buffer, with their conversions
+    chans: Vec<OutputChannel>,
+    /// Scratch space for the raw values of a channel, in the host format
+    raw: Vec<u8>,
+}
+
+/// An output channel, with the values to convert samples for it.
+#[derive(Debug)]
+struct OutputChannel {
+    /// The channel
+    chan: Channel,
+    /// The size of a raw value, in bytes
+    size: usize,
+    /// The conversion from physical units to raw values
+    conv: RawConversion,
+}
+
+impl OutputChannel {
+    /// Gets the conversion values for the channel.
+    fn new(chan: &Channel) -> Result<Self> {
+        let fmt = chan.data_format();
+        let size = fmt.byte_length();
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return Err(Error::WrongDataType);
+        }
+        let conv = RawConversion::new(
+            fmt.bits(),
+            fmt.is_signed(),
+            chan.offset()?.unwrap_or(0.0),
+            chan.scale()?.unwrap_or(1.0),
+        );
+        Ok(Self {
+            chan: chan.clone(),
+            size,
+            conv,
+        })
+    }
+
+    /// Converts a value in physical units to a raw value for the channel,
+    /// and appends it to the bytes in the host format.
+    fn push_raw(&self, raw: &mut Vec<u8>, val: f64) {
+        // Truncating to the size of the channel keeps the bit pattern of
+        // the raw value, whether it's signed or unsigned.
+        let v = self.conv.to_raw(val);
+        match self.size {
+            1 => raw.extend_from_slice(&(v as u8).to_ne_bytes()),
+            2 => raw.extend_from_slice(&(v as u16).to_ne_bytes()),
+            4 => raw.extend_from_slice(&(v as u32).to_ne_bytes()),
+            _ => raw.extend_from_slice(&v.to_ne_bytes()),
+        }
+    }
+}
+
+/// The conversion of values in physical units to the raw values of an
+/// output channel, as `value / scale - offset`, clamped to the range of
+/// the channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RawConversion {
+    /// The channel's offset, in raw units
+    offset: f64,
+    /// The channel's scale
+    scale: f64,
+    /// The smallest raw value that the channel can hold
+    min: f64,
+    /// The largest raw value that the channel can hold
+    max: f64,
+}
+
+impl RawConversion {
+    /// Creates the conversion for a channel with the number of bits and
+    /// signedness of its data format, and its offset and scale.
+    fn new(bits: u32, signed: bool, offset: f64, scale: f64) -> Self {
+        let bits = bits.clamp(1, 64);
+        let (min, max) = if signed {
+            let max = (1u64 << (bits - 1)) as f64;
+            (-max, max - 1.0)
+        }
+        else {
+            (0.0, ((1u128 << bits) - 1) as f64)
+        };
+        Self {
+            offset,
+            scale,
+            min,
+            max,
+        }
+    }
+
+    /// Converts a value in physical units to a raw value, clamped to the
+    /// range of the channel.
+    ///
+    /// For an unsigned 64-bit channel, values above `i64::MAX` are returned
+    /// with the same bit pattern as the `u64` value.
+    fn to_raw(self, val: f64) -> i64 {
+        let raw = (val / self.scale - self.offset)
+            .round()
+            .max(self.min)
+            .min(self.max);
+        if self.min < 0.0 {
+            raw as i64
+        }
+        else {
+            raw as u64 as i64
+        }
+    }
+}
+
+impl OutputStream {
+    /// Creates an output stream from a buffer.
+    ///
+    /// The buffer must not be cyclic, and the channels that were enabled
+    /// when it was created must be output channels.
+    pub fn new(buf: Buffer) -> Result<Self> {
+        if buf.is_cyclic() {
+            return Err(Error::General(
+                "A cyclic buffer can't be used for continuous output".into(),
+            ));
+        }
+        if buf.chans.is_empty() {
+            return Err(Error::General("The buffer has no channels".into()));
+        }
+        if let Some(ch) = buf.chans.iter().find(|ch| !ch.is_output()) {
+            return Err(Error::General(format!(
+                "The channel '{}' is not an output channel",
+                ch.id().unwrap_or_default()
+            )));
+        }
+
+        let chans = buf
+            .chans
+            .iter()
+            .map(OutputChannel::new)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            buf,
+            chans,
+            raw: Vec::new(),
+        })
+    }
+
+    /// Creates an output stream for exactly the given channels of the
+    /// device, with a buffer of `depth` samples.
+    ///
+    /// See [`Device::create_buffer_for_channels()`].
+    pub fn with_channels(dev: &Device, chans: &[&Channel], depth: usize) -> Result<Self> {
+        if let Some(ch) = chans.iter().find(|ch| !ch.is_output()) {
+            return Err(Error::General(format!(
+                "The channel '{}' is not an output channel",
+                ch.id().unwrap_or_default()
+            )));
+        }
+        let buf = dev.create_buffer_for_channels(chans, depth, false)?;
+        Self::new(buf)
+    }
+
+    /// Gets a reference to the buffer used by the stream.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buf
+    }
+
+    /// Gets a mutable reference to the buffer used by the stream.
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+
+    /// Consumes the stream, returning the underlying buffer.
+    pub fn into_buffer(self) -> Buffer {
+        self.buf
+    }
+
+    /// Gets the number of channels in the stream.
+    pub fn num_channels(&self) -> usize {
+        self.chans.len()
+    }
+
+    /// Writes a window of samples to the hardware.
+    ///
+    /// The data holds values in physical units, interleaved by sample, with
+    /// one value for each channel, in the order of
+    /// [`Buffer::channels()`]. Each value is converted to the hardware
+    /// format as `value / scale - offset`, using the channel's
+    /// [scale][Channel::scale()] and [offset][Channel::offset()], if it has
+    /// them, and clamped to the range of the channel. The buffer is then
+    /// pushed.
+    ///
+    /// The window can hold at most the capacity of the buffer. A shorter
+    /// window is pushed as a partial buffer.
+    pub fn write_window(&mut self, data: &[f64]) -> Result<()> {
+        let nchan = self.chans.len();
+        let n = data.len() / nchan;
+        if n * nchan != data.len() {
+            return Err(Error::General(format!(
+                "The window of {} values doesn't hold a whole number of samples for {} channels",
+                data.len(),
+                nchan
+            )));
+        }
+
+        let cap = self.buf.capacity();
+        if n > cap {
+            return Err(Error::General(format!(
+                "The window has {} samples, but the buffer only holds {}",
+                n, cap
+            )));
+        }
+
+        for (i, oc) in self.chans.iter().enumerate() {
+            self.raw.clear();
+            for &val in data.iter().skip(i).step_by(nchan) {
+                oc.push_raw(&mut self.raw, val);
+            }
+            oc.chan.write_bytes(&self.buf, &self.raw);
+        }
+
+        if n == cap {
+            self.buf.push()?;
+        }
+        else {
+            self.buf.push_partial(n)?;
+        }
+        Ok(())
+    }
+}
+
+/// A builder to configure and start a capture from a device.
+///
+/// Setting up a capture requires several steps that must be done in the
+/// right order. The builder collects the settings, then
+/// [`CaptureBuilder::start()`] validates them and applies them in turn:
+///
+/// 1. Sets the trigger for the device, if one was given.
+/// 2. Sets the sampling frequency, if one was given. This is set on the
+///    device if it has a `sampling_frequency` attribute, otherwise on the
+///    trigger.
+/// 3. Enables the requested channels, and disables all the others.
+/// 4. Creates the buffer and sets its blocking mode.
+#[derive(Debug)]
+pub struct CaptureBuilder {
+    /// The device to capture from
+    dev: Device,
+    /// The IDs of the channels to enable
+    chan_ids: Vec<String>,
+    /// The trigger for the device
+    trigger: Option<Device>,
+    /// The number of samples in the buffer
+    depth: usize,
+    /// Whether the buffer should be cyclic
+    cyclic: bool,
+    /// Whether refills should block
+    blocking: bool,
+    /// The requested sampling frequency, in Hz
+    freq: Option<f64>,
+}
+
+impl CaptureBuilder {
+    /// The default number of samples in the buffer.
+    pub const DFLT_DEPTH: usize = 1024;
+
+    /// Creates a builder for a capture from the device.
+    pub fn new(dev: &Device) -> Self {
+        Self {
+            dev: dev.clone(),
+            chan_ids: Vec::new(),
+            trigger: None,
+            depth: Self::DFLT_DEPTH,
+            cyclic: false,
+            blocking: true,
+            freq: None,
+        }
+    }
+
+    /// Adds an input channel, by ID, to enable for the capture.
+    pub fn channel(mut self, id: &str) -> Self {
+        self.chan_ids.push(id.to_string());
+        self
+    }
+
+    /// Adds a number of input channels, by ID, to enable for the capture.
+    pub fn channels(mut self, ids: &[&str]) -> Self {
+        self.chan_ids.extend(ids.iter().map(|id| id.to_string()));
+        self
+    }
+
+    /// Sets the trigger for the device.
+    pub fn trigger(mut self, trigger: &Device) -> Self {
+        self.trigger = Some(trigger.clone());
+        self
+    }
+
+    /// Sets the number of samples in the buffer.
+    pub fn depth(mut self, n: usize) -> Self {
+        self.depth = n;
+        self
+    }
+
+    /// Sets whether the buffer is cyclic.
+    ///
+    /// Cyclic buffers are only valid for output, so this must be false to
+    /// start an input capture.
+    pub fn cyclic(mut self, on: bool) -> Self {
+        self.cyclic = on;
+        self
+    }
+
+    /// Sets whether refills of the buffer block. The default is true.
+    pub fn blocking(mut self, on: bool) -> Self {
+        self.blocking = on;
+        self
+    }
+
+    /// Sets the sampling frequency, in Hz.
+    pub fn sampling_frequency(mut self, hz: f64) -> Self {
+        self.freq = Some(hz);
+        self
+    }
+
+    /// Validates the settings, configures the device, and starts the
+    /// capture.
+    pub fn start(self) -> Result<InputStream> {
+        if self.cyclic {
+            return Err(Error::General(
+                "A cyclic buffer can't be used for input capture".into(),
+            ));
+        }
+        if self.depth == 0 {
+            return Err(Error::General("The buffer depth must be non-zero".into()));
+        }
+        if self.chan_ids.is_empty() {
+            return Err(Error::General(
+                "No channels were selected for the capture".into(),
+            ));
+        }
+
+        let chans = self
+            .chan_ids
+            .iter()
+            .map(|id| match self.dev.find_channel(id, false) {
+                Some(ch) if ch.is_scan_element() => Ok(ch),
+                Some(_) => Err(Error::General(format!(
+                    "The channel '{}' can't be used for buffered capture",
+                    id
+                ))),
+                None => Err(Error::General(format!("No input channel '{}'", id))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(ref trig) = self.trigger {
+            self.dev.set_trigger(trig)?;
+        }
+
+        if let Some(hz) = self.freq {
+            if self.dev.has_attr(SAMPLING_FREQ_ATTR) {
+                self.dev.attr_write_float(SAMPLING_FREQ_ATTR, hz)?;
+            }
+            else {
+                match self.dev.trigger()? {
+                    Some(trig) if trig.has_attr(SAMPLING_FREQ_ATTR) => {
+                        trig.attr_write_float(SAMPLING_FREQ_ATTR, hz)?
+                    }
+                    _ => {
+                        return Err(Error::General(
+                            "Neither the device nor its trigger has a sampling frequency".into(),
+                        ))
+                    }
+                }
+            }
+        }
+
+        for ch in self.dev.channels() {
+            ch.disable();
+        }
+        for ch in &chans {
+            ch.enable();
+        }
+
+        let buf = self.dev.create_buffer(self.depth, false)?;
+        buf.set_blocking_mode(self.blocking)?;
+        Ok(InputStream::new(buf))
+    }
+}
+
+// Gets the raw, decoded samples for a channel from the last refill of the
+// buffer, or None if the channel isn't part of the buffer.
+fn raw_samples(buf: &Buffer, chan: &Channel) -> Option<Vec<i64>> {
+    if !buf.chans.iter().any(|ch| ch.chan == chan.chan) {
+        return None;
+    }
+
+    let fmt = chan.data_format();
+    let sz = fmt.byte_length();
+    let step = buf.step();
+    if sz == 0 || step == 0 {
+        return Some(Vec::new());
+    }
+
+    unsafe {
+        let start = ffi::iio_buffer_start(buf.buf) as *const u8;
+        let first = ffi::iio_buffer_first(buf.buf, chan.chan) as *const u8;
+        let end = start.wrapping_add(buf.len());
+
+        let mut v = Vec::new();
+        let mut p = first;
+        while p.wrapping_add(sz) <= end {
+            v.push(fmt.decode(slice::from_raw_parts(p, sz)));
+            p = p.wrapping_add(step);
+        }
+        Some(v)
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Successive counts, with and without wrapping, aren't drops.
+    #[test]
+    fn count_step_forward() {
+        assert_eq!(count_step(5, 6, 0xFF), None);
+        assert_eq!(count_step(0xFF, 0, 0xFF), None);
+        assert_eq!(count_step(u64::MAX, 0, u64::MAX), None);
+    }
+
+    // Gaps in the count, with and without wrapping, report the missed samples.
+    #[test]
+    fn count_step_gap() {
+        assert_eq!(count_step(5, 8, 0xFF), Some(2));
+        assert_eq!(count_step(0xFE, 2, 0xFF), Some(3));
+        assert_eq!(count_step(10, 10 + 0x80, 0xFF), Some(0x7F));
+        assert_eq!(count_step(u64::MAX - 1, 3, u64::MAX), Some(4));
+    }
+
+    // Repeated and backward counts are resets, with no samples missed.
+    #[test]
+    fn count_step_reset() {
+        assert_eq!(count_step(5, 5, 0xFF), Some(0));
+        assert_eq!(count_step(100, 0, 0xFF), Some(0));
+        assert_eq!(count_step(100, 99, 0xFF), Some(0));
+        assert_eq!(count_step(1000, 0, u64::MAX), Some(0));
+        assert_eq!(count_step(7, 7, u64::MAX), Some(0));
+    }
+
+    // Values are converted to raw units with the offset and scale.
+    #[test]
+    fn to_raw_scaled() {
+        let conv = RawConversion::new(12, false, 100.0, 0.5);
+        assert_eq!(conv.to_raw(100.0), 100);
+        assert_eq!(conv.to_raw(-50.0), 0);
+        assert_eq!(conv.to_raw(60.2), 20);
+
+        let conv = RawConversion::new(16, true, 0.0, 1.0);
+        assert_eq!(conv.to_raw(-1234.4), -1234);
+        assert_eq!(conv.to_raw(1234.6), 1235);
+    }
+
+    // Values are clamped to the range of signed channels.
+    #[test]
+    fn to_raw_clamp_signed() {
+        let conv = RawConversion::new(12, true, 0.0, 1.0);
+        assert_eq!(conv.to_raw(5000.0), 2047);
+        assert_eq!(conv.to_raw(-5000.0), -2048);
+
+        let conv = RawConversion::new(64, true, 0.0, 1.0);
+        assert_eq!(conv.to_raw(1e30), i64::MAX);
+        assert_eq!(conv.to_raw(-1e30), i64::MIN);
+    }
+
+    // Values are clamped to the range of unsigned channels.
+    #[test]
+    fn to_raw_clamp_unsigned() {
+        let conv = RawConversion::new(8, false, 0.0, 1.0);
+        assert_eq!(conv.to_raw(300.0), 255);
+        assert_eq!(conv.to_raw(-3.0), 0);
+
+        let conv = RawConversion::new(32, false, 0.0, 1.0);
+        assert_eq!(conv.to_raw(1e12), u32::MAX as i64);
+
+        // A 64-bit value keeps the bit pattern of the u64
+        let conv = RawConversion::new(64, false, 0.0, 1.0);
+        assert_eq!(conv.to_raw(1e30) as u64, u64::MAX);
+        assert_eq!(conv.to_raw(1e19) as u64, 10_000_000_000_000_000_000);
+        assert_eq!(conv.to_raw(-1.0), 0);
+    }
+}